@@ -1,79 +1,104 @@
 use crate::{
-    primitives::new_moka_cache,
-    server::{AppState, Service},
-    store::GameTransaction,
+    mines::{generate_seed, hash_seed},
+    primitives::{Amount, ApiError, ApiErrorCode, CodedResult, WithErrorCode, bet_request},
+    random_server,
+    server::{AppState, DEFAULT_APEX_NUMBER_MAX, GameConfig, Service},
+    store::{ApexRound, GameTransaction, User},
 };
-use axum::{Router, extract::State, response::Json, routing::post, Extension};
+use axum::{Router, extract::{State, rejection::JsonRejection}, response::Json, routing::post, Extension};
 use garden::api::{
     bad_request, internal_error,
-    primitives::{ApiResult, Response},
+    primitives::Response,
 };
+use bigdecimal::ToPrimitive;
+use hmac::{Hmac, Mac};
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 use serde_json::to_value;
+use sha2::Sha256;
 use sqlx::types::BigDecimal;
-use std::{sync::Arc, time::Duration, str::FromStr};
+use std::sync::Arc;
 use uuid::Uuid;
 use once_cell::sync::Lazy;
 use std::env;
 
-const SESSION_TTL: Duration = Duration::from_secs(30 * 60);
 static RANDOM_SERVER_URL: Lazy<String> = Lazy::new(|| {
     env::var("RANDOM_SERVER_URL")
         .unwrap_or_else(|_| "http://localhost:3000".to_string())
 });
 
+// RANDOM_SERVER_STRICT=1 fails games when the random server can't be reached, rather
+// than falling back to a local draw
+static RANDOM_SERVER_STRICT: Lazy<bool> =
+    Lazy::new(|| env::var("RANDOM_SERVER_STRICT").as_deref() == Ok("1"));
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct RandomNumberResponse {
-    success: bool,
-    #[serde(rename = "randomNumber")]
-    random_number: u32,
+// A uniform number in 0..count from the random-verifiable-server
+async fn draw_random_number(count: u64) -> eyre::Result<u32> {
+    get_random_below_with_fallback(&RANDOM_SERVER_URL, *RANDOM_SERVER_STRICT, count).await
 }
 
-// Function to get random number from random-verifiable-server
-async fn get_random_number() -> eyre::Result<u32> {
-    let client = reqwest::Client::new();
-    let response = client
-        .get(&format!("{}/random", RANDOM_SERVER_URL.as_str()))
-        .send()
-        .await
-        .map_err(|e| eyre::eyre!("Failed to request random number: {}", e))?;
-    
-    if !response.status().is_success() {
-        return Err(eyre::eyre!("Random server returned error: {}", response.status()));
-    }
-    
-    let random_response: RandomNumberResponse = response
-        .json()
-        .await
-        .map_err(|e| eyre::eyre!("Failed to parse random number response: {}", e))?;
-    
-    if !random_response.success {
-        return Err(eyre::eyre!("Random server indicated failure"));
+// The server is retried first, so only a lasting outage falls back to a local draw
+async fn get_random_below_with_fallback(server_url: &str, strict: bool, count: u64) -> eyre::Result<u32> {
+    match random_server::get_random_below(server_url, count).await {
+        Ok(number) => Ok(number as u32),
+        Err(e) if strict => Err(e),
+        Err(e) => {
+            tracing::warn!("Random server unavailable, using local randomness: {}", e);
+            Ok(rand::thread_rng().gen_range(0..count) as u32)
+        }
     }
-    
-    Ok(random_response.random_number)
+}
+
+// Derive a 0..=number_max apex number from the seeds: the first big-endian u64 of
+// HMAC-SHA256(server_seed, "client_seed:nonce:round") reduced mod number_max + 1. Round 0
+// is the system number and round 1 the user number, so the revealed seeds reproduce both.
+// Later rounds of a multi-round match carry on in pairs: 2 and 3, then 4 and 5, and so on
+pub fn derive_apex_number(server_seed: &str, client_seed: &str, nonce: u64, round: u64, number_max: u32) -> u32 {
+    let mut mac = Hmac::<Sha256>::new_from_slice(server_seed.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(format!("{}:{}:{}", client_seed, nonce, round).as_bytes());
+    let digest = mac.finalize().into_bytes();
+    (u64::from_be_bytes(digest[..8].try_into().unwrap()) % (number_max as u64 + 1)) as u32
+}
+
+// Sessions saved before the range was configurable used single digits
+fn default_number_max() -> u32 {
+    DEFAULT_APEX_NUMBER_MAX
+}
+
+// Longest match a single apex game can be
+pub const MAX_APEX_ROUNDS: u32 = 10;
+
+// Games and sessions from before multi-round matches were a single round
+fn default_rounds() -> u32 {
+    1
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StartGameRequest {
-    pub game_address: String,
-    pub amount: f64,
+    pub amount: Amount,
     pub option: GameOption,
+    pub client_seed: Option<String>, // Generated server-side when omitted
+    pub nonce: Option<u64>,
+    #[serde(default)]
+    pub verifiable: bool, // Draw numbers from the random server instead of the seeds (slower)
+    #[serde(default = "default_rounds")]
+    pub rounds: u32, // Best of N: the amount is staked on every round, all of it up front
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum GameOption {
     Blinder,
     NonBlinder,
+    Hidden, // Like NonBlinder, but the system number stays secret until the choice is made
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StartGameResponse {
     pub id: String,
-    pub amount: f64,
+    pub amount: Amount,
     pub option: GameOption,
-    pub system_number: u32,
+    pub system_number: Option<u32>, // Withheld for hidden games, which reveal it on the choice
     pub user_number: Option<u32>, // Only for blinder mode
     pub payout_high: Option<f64>,
     pub probability_high: Option<f64>,
@@ -81,31 +106,38 @@ pub struct StartGameResponse {
     pub probability_low: Option<f64>,
     pub payout_equal: Option<f64>,
     pub probability_equal: Option<f64>,
+    pub available_choices: Option<Vec<Choice>>, // Only the choices that can win, for non-blinder
     pub payout_percentage: Option<f64>,    // Only for blinder
     pub blinder_suit: Option<BlinderSuit>, // Only for blinder mode
+    pub server_seed_hash: String,
+    pub client_seed: String,
+    pub nonce: u64,
+    pub server_seed: Option<String>, // Revealed once the game has ended (blinder)
+    pub rounds: u32,
     pub session_status: SessionStatus,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BlinderSuit {
     pub won: bool,
-    pub payout: f64,
+    pub payout: f64,          // Credited amount, clamped to the max payout
+    pub uncapped_payout: f64, // Payout before the clamp
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChooseRequest {
-    pub game_address: String,
     pub id: String,
     pub choice: Choice,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Choice {
     High,
     Low,
     Equal,
 }
 
+// Numbers and payout of the round just played
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChooseResponse {
     pub id: String,
@@ -113,18 +145,70 @@ pub struct ChooseResponse {
     pub user_number: u32,
     pub system_number: u32,
     pub won: bool,
-    pub payout: f64,
+    pub payout_multiplier: f64, // Priced from the system number, so hidden games learn it here
+    pub payout: f64,          // Round payout, clamped to the max payout
+    pub uncapped_payout: f64, // Payout before the clamp
+    pub round: u32, // Counting from 1
+    pub rounds_remaining: u32,
+    pub next_system_number: Option<u32>, // The next round's number, unless the match ended or it's hidden
+    pub available_choices: Option<Vec<Choice>>, // For the next round, alongside its number
+    pub round_results: Vec<RoundResult>, // Every round played so far, this one included
+    pub total_payout: f64, // Sum of the round payouts up to the max payout, credited once the match ends
+    pub server_seed: Option<String>, // Revealed once the last round is played
     pub session_status: SessionStatus,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoundResult {
+    pub choice: Choice,
+    pub system_number: u32,
+    pub user_number: u32,
+    pub won: bool,
+    pub payout_multiplier: f64,
+    pub payout: f64, // Clamped to the max payout
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GameSession {
     pub id: String,
+    pub user_id: String,
     pub amount: f64,
     pub option: GameOption,
     pub system_number: u32,
     pub user_number: Option<u32>,
+    pub server_seed: String, // Secret until the game ends
+    pub server_seed_hash: String,
+    pub client_seed: String,
+    pub nonce: u64,
+    pub verifiable: bool, // Numbers come from the random server rather than the seeds
+    #[serde(default = "default_number_max")]
+    pub number_max: u32, // Range in play when the session started, kept if the config changes
     pub status: SessionStatus,
+    #[serde(default)]
+    pub bonus_wagered: BigDecimal, // Part of the bet counted towards a bonus wagering requirement
+    #[serde(default)]
+    pub round_results: Vec<RoundResult>,
+    #[serde(default = "default_rounds")]
+    pub rounds_remaining: u32, // Including the round being played
+    #[serde(default)]
+    pub version: u64, // Bumped on every save, see AppState::save_session
+}
+
+// Client-safe snapshot of a session: omits the unrevealed server seed, and the system
+// number of a hidden game
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionView {
+    pub id: String,
+    pub amount: f64,
+    pub option: GameOption,
+    pub system_number: Option<u32>,
+    pub available_choices: Option<Vec<Choice>>, // Withheld along with a hidden system number
+    pub server_seed_hash: String,
+    pub client_seed: String,
+    pub nonce: u64,
+    pub round_results: Vec<RoundResult>,
+    pub rounds_remaining: u32,
+    pub session_status: SessionStatus,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -134,73 +218,220 @@ pub enum SessionStatus {
 }
 
 impl GameSession {
-    pub async fn new(amount: f64, option: GameOption) -> eyre::Result<Self> {
-        let system_number = get_random_number().await?;
+    pub async fn new(
+        amount: f64,
+        option: GameOption,
+        user_id: String,
+        client_seed: String,
+        nonce: u64,
+        verifiable: bool,
+        number_max: u32,
+    ) -> eyre::Result<Self> {
+        // Provably-fair numbers: commit to a fresh server seed, reveal it when the game ends
+        let mut session = Self::from_seeds(amount, option, user_id, generate_seed(), client_seed, nonce, number_max);
+        if verifiable {
+            session.use_random_number(draw_random_number(session.number_count()).await?);
+            if session.user_number.is_some() {
+                // Blinder's number is a second, independent draw, as the blinder odds assume
+                session.user_number = Some(draw_random_number(session.number_count()).await?);
+            }
+        }
+        Ok(session)
+    }
+
+    fn from_seeds(
+        amount: f64,
+        option: GameOption,
+        user_id: String,
+        server_seed: String,
+        client_seed: String,
+        nonce: u64,
+        number_max: u32,
+    ) -> Self {
+        let system_number = derive_apex_number(&server_seed, &client_seed, nonce, 0, number_max);
         let user_number = match option {
-            GameOption::Blinder => {
-                // For blinder mode, derive user number from system number to avoid second blockchain call
-                // This ensures both numbers are cryptographically random but only requires one blockchain call
-                let derived = ((system_number as u64 * 7 + 3) % 10) as u32;
-                Some(derived)
-            },
-            GameOption::NonBlinder => None,
+            GameOption::Blinder => Some(derive_apex_number(&server_seed, &client_seed, nonce, 1, number_max)),
+            GameOption::NonBlinder | GameOption::Hidden => None,
         };
-        Ok(GameSession {
+        GameSession {
             id: Uuid::new_v4().to_string(),
+            user_id,
             amount,
             option,
             system_number,
             user_number,
+            server_seed_hash: hash_seed(&server_seed),
+            server_seed,
+            client_seed,
+            nonce,
+            verifiable: false,
+            number_max,
             status: SessionStatus::Active,
-        })
+            bonus_wagered: BigDecimal::from(0),
+            round_results: Vec::new(),
+            rounds_remaining: 1,
+            version: 0,
+        }
+    }
+
+    // How many numbers can be drawn, 0 through number_max
+    fn number_count(&self) -> u64 {
+        self.number_max as u64 + 1
+    }
+
+    // Replaces the seeded system number with a random server draw. Draws already fall in
+    // the session's range; reducing again keeps an out-of-range value from breaking the odds
+    fn use_random_number(&mut self, random_number: u32) {
+        self.verifiable = true;
+        self.system_number = (random_number as u64 % self.number_count()) as u32;
+    }
+
+    // The system number as clients may see it: hidden games keep it back while active
+    pub fn visible_system_number(&self) -> Option<u32> {
+        let hidden = matches!(self.option, GameOption::Hidden) && self.status == SessionStatus::Active;
+        (!hidden).then_some(self.system_number)
     }
 
-    pub fn get_choice_info(&self, choice: &Choice) -> (f64, f64) {
+    // Everything staked on the match, one bet per round
+    pub fn stake(&self) -> f64 {
+        self.amount * (self.round_results.len() as u32 + self.rounds_remaining) as f64
+    }
+
+    // What an abandoned game hands back: the payouts of rounds already won, plus the bet on
+    // every round whose system number the player hasn't seen. A hidden game's number is
+    // still secret, so its current round counts as unseen
+    pub fn abandoned_refund(&self) -> Option<BigDecimal> {
+        if self.status != SessionStatus::Active {
+            return None;
+        }
+        let seen = self.visible_system_number().is_some() as u32;
+        let won: f64 = self.round_results.iter().map(|r| r.payout).sum();
+        let refund = won + self.amount * self.rounds_remaining.saturating_sub(seen) as f64;
+        (refund > 0.0).then(|| Amount::try_from(refund).ok().map(Amount::into_inner)).flatten()
+    }
+
+    pub fn view(&self) -> SessionView {
+        SessionView {
+            id: self.id.clone(),
+            amount: self.amount,
+            option: self.option.clone(),
+            system_number: self.visible_system_number(),
+            available_choices: self.visible_system_number().map(|_| self.available_choices()),
+            server_seed_hash: self.server_seed_hash.clone(),
+            client_seed: self.client_seed.clone(),
+            nonce: self.nonce,
+            round_results: self.round_results.clone(),
+            rounds_remaining: self.rounds_remaining,
+            session_status: self.status.clone(),
+        }
+    }
+
+    // The odds assume the user number is drawn uniformly and independently from the same
+    // 0..=number_max as the system number, which every draw (derive_apex_number, or server
+    // digits combined by random_server::get_random_below) keeps to. Then High, Low and Equal
+    // cover every user number exactly once and their probabilities sum to one
+    pub fn get_choice_info(&self, choice: &Choice, config: &GameConfig) -> (f64, f64) {
+        let count = self.number_count() as f64;
         let true_probability = match choice {
-            Choice::High => (9.0 - self.system_number as f64) / 10.0,
-            Choice::Low => self.system_number as f64 / 10.0,
-            Choice::Equal => 1.0 / 10.0, // Always 1/10 = 0.1 (10% chance)
-        };
-        let payout = if true_probability > 0.0 {
-            (1.0 - 0.01) / true_probability
-        } else {
-            0.0
+            Choice::High => (self.number_max as f64 - self.system_number as f64) / count,
+            Choice::Low => self.system_number as f64 / count,
+            Choice::Equal => 1.0 / count, // One number in the range matches
         };
-        (true_probability, payout)
+        (true_probability, config.payout_multiplier(true_probability))
+    }
+
+    // Choices that can still win against the system number. High is dead when it sits at
+    // number_max and Low when it is 0; Equal always has one number to match
+    pub fn available_choices(&self) -> Vec<Choice> {
+        [Choice::High, Choice::Low, Choice::Equal]
+            .into_iter()
+            .filter(|choice| match choice {
+                Choice::High => self.system_number < self.number_max,
+                Choice::Low => self.system_number > 0,
+                Choice::Equal => true,
+            })
+            .collect()
     }
 
-    pub async fn make_choice(&mut self, choice: Choice) -> eyre::Result<ChooseResponse> {
+    pub async fn make_choice(
+        &mut self,
+        choice: Choice,
+        max_payout: f64,
+        config: &GameConfig,
+    ) -> eyre::Result<ChooseResponse> {
         if self.status != SessionStatus::Active {
             return Err(eyre::eyre!("Session is not active"));
         }
         if matches!(self.option, GameOption::Blinder) {
             return Err(eyre::eyre!("Cannot make choice in blinder mode"));
         }
-        self.status = SessionStatus::Ended;
-        let user_number = get_random_number().await?;
-        let (_prob, payout_multiplier) = self.get_choice_info(&choice);
+        // A hidden game's player picks blind, so only a visible number rules a choice out
+        if self.visible_system_number().is_some() && !self.available_choices().contains(&choice) {
+            return Err(eyre::eyre!("{:?} can't win against a system number of {}", choice, self.system_number));
+        }
+        let round = self.round_results.len() as u64;
+        let user_number = self.draw_number(2 * round + 1).await?;
+        let (_prob, payout_multiplier) = self.get_choice_info(&choice, config);
         let won = match choice {
             Choice::High => user_number > self.system_number,
             Choice::Low => user_number < self.system_number,
             Choice::Equal => user_number == self.system_number,
         };
-        let payout = if won {
+        let uncapped_payout = if won {
             self.amount * payout_multiplier
         } else {
             0.0
         };
+        let system_number = self.system_number;
+        self.round_results.push(RoundResult {
+            choice: choice.clone(),
+            system_number,
+            user_number,
+            won,
+            payout_multiplier,
+            payout: uncapped_payout.min(max_payout),
+        });
+        self.rounds_remaining = self.rounds_remaining.saturating_sub(1);
+        if self.rounds_remaining == 0 {
+            self.status = SessionStatus::Ended;
+        } else {
+            self.system_number = self.draw_number(2 * (round + 1)).await?;
+        }
+
+        // The match is paid as one win, so its total is capped like any single payout
+        let total_payout = self.round_results.iter().map(|r| r.payout).sum::<f64>().min(max_payout);
+        let next_system_number = self.visible_system_number().filter(|_| self.rounds_remaining > 0);
+
         Ok(ChooseResponse {
             id: self.id.clone(),
             choice: Some(choice),
             user_number,
-            system_number: self.system_number,
+            system_number,
             won,
-            payout,
+            payout_multiplier,
+            payout: uncapped_payout.min(max_payout),
+            uncapped_payout,
+            round: round as u32 + 1,
+            rounds_remaining: self.rounds_remaining,
+            next_system_number,
+            available_choices: next_system_number.map(|_| self.available_choices()),
+            round_results: self.round_results.clone(),
+            total_payout,
+            server_seed: (self.status == SessionStatus::Ended).then(|| self.server_seed.clone()),
             session_status: self.status.clone(),
         })
     }
 
-    pub fn get_blinder_result(&mut self) -> eyre::Result<BlinderSuit> {
+    // The number at `index` of the seeded sequence, or a fresh one for verifiable games
+    async fn draw_number(&self, index: u64) -> eyre::Result<u32> {
+        if self.verifiable {
+            draw_random_number(self.number_count()).await
+        } else {
+            Ok(derive_apex_number(&self.server_seed, &self.client_seed, self.nonce, index, self.number_max))
+        }
+    }
+
+    pub fn get_blinder_result(&mut self, max_payout: f64, config: &GameConfig) -> eyre::Result<BlinderSuit> {
         if self.status != SessionStatus::Active {
             return Err(eyre::eyre!("Session is not active"));
         }
@@ -210,39 +441,173 @@ impl GameSession {
         self.status = SessionStatus::Ended;
         let user_number = self.user_number.unwrap();
         let won = user_number > self.system_number; // Draw means system wins
-        let probability = 0.45; // 45% chance of winning (user_number > system_number)
-        let payout_multiplier = (1.0 - 0.01) / probability; // 1% house edge
-        let payout = if won {
+        let payout_multiplier = config.payout_multiplier(config.blinder_win_prob);
+        let uncapped_payout = if won {
             self.amount * payout_multiplier
         } else {
             0.0
         };
-        Ok(BlinderSuit { won, payout })
+        Ok(BlinderSuit {
+            won,
+            payout: uncapped_payout.min(max_payout),
+            uncapped_payout,
+        })
     }
 }
 
-async fn start_game(
-    State(state): State<Arc<AppState>>,
-    Extension(user_addr): Extension<String>,
-    Json(payload): Json<StartGameRequest>,
-) -> ApiResult<StartGameResponse> {
-    // Get user from database
-    let user = state.store.get_user_by_wallet_addr(&user_addr).await
-        .map_err(|e| internal_error(&format!("Database error: {}", e)))?
-        .ok_or_else(|| bad_request("User not found"))?;
+// Expected return per option, with both numbers drawn uniformly from the range
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApexRtp {
+    pub blinder: f64,
+    pub non_blinder: f64, // Playing any choice that can still win
+    pub hidden: f64, // Choosing blind, without seeing the system number
+}
+
+pub fn theoretical_rtp(config: &GameConfig) -> ApexRtp {
+    let count = config.apex_number_max as f64 + 1.0;
+    // Every choice is priced off its own odds, so any winnable one returns the same. Equal
+    // always is; high and low are dead when the system number sits at their end of the range
+    let winnable = (1.0 / count) * config.payout_multiplier(1.0 / count);
+    ApexRtp {
+        // A draw goes to the house
+        blinder: (count - 1.0) / (2.0 * count) * config.payout_multiplier(config.blinder_win_prob),
+        non_blinder: winnable,
+        hidden: winnable * (3.0 * count - 2.0) / (3.0 * count),
+    }
+}
+
+// The payout cap as f64, for apex's floating-point payout math
+pub fn max_payout_f64(state: &AppState) -> f64 {
+    state.max_payout.to_f64().unwrap_or(f64::MAX)
+}
+
+// Persist a resolved apex round alongside its transactions
+pub async fn record_apex_round(
+    state: &AppState,
+    session: &GameSession,
+    round: u32,
+    choice: Option<String>,
+    system_number: u32,
+    user_number: u32,
+    won: bool,
+    payout: f64,
+) -> Result<(), Response<()>> {
+    let round = ApexRound {
+        id: String::new(),
+        session_id: session.id.clone(),
+        round: round as i32,
+        user_id: session.user_id.clone(),
+        option: format!("{:?}", session.option),
+        system_number: system_number as i32,
+        user_number: user_number as i32,
+        choice,
+        won,
+        payout: Amount::try_from(payout)
+            .map_err(|_| internal_error("Invalid payout amount"))?
+            .into_inner(),
+        created_at: None,
+    };
+    state.store.create_apex_round(&round).await
+        .map_err(|e| internal_error(&format!("Failed to record apex round: {}", e)))?;
+    Ok(())
+}
+
+// Shared core of the apex start routes: takes the bet, creates the session, settles
+// blinder games on the spot and keeps non-blinder sessions until a choice is made
+pub async fn resolve_start(
+    state: &AppState,
+    user: &User,
+    payload: StartGameRequest,
+) -> Result<StartGameResponse, ApiError> {
+    state.ensure_betting_open()?;
+    let bet_amount = payload.amount.as_decimal().clone();
+    state.bet_limits.validate(&bet_amount)
+        .map_err(|e| bad_request(e).with_code(ApiErrorCode::InvalidAmount))?;
+    if !(1..=MAX_APEX_ROUNDS).contains(&payload.rounds) {
+        return Err(bad_request(&format!("Rounds must be between 1 and {}", MAX_APEX_ROUNDS)).into());
+    }
+    if payload.rounds > 1 && matches!(payload.option, GameOption::Blinder) {
+        return Err(bad_request("Blinder games are played in a single round").into());
+    }
+    // The whole match is paid for at the start, so its total stake must fit the limit too
+    let bet_amount = bet_amount * BigDecimal::from(payload.rounds);
+    if bet_amount > state.bet_limits.max {
+        return Err(bad_request("Total stake over all rounds is above the maximum bet")
+            .with_code(ApiErrorCode::InvalidAmount));
+    }
+
+    // Created before the bet is taken, so a random server outage costs the player nothing
+    let client_seed = payload.client_seed.clone().unwrap_or_else(generate_seed);
+    let mut session = GameSession::new(
+        payload.amount.to_f64(),
+        payload.option.clone(),
+        user.user_id.clone(),
+        client_seed,
+        payload.nonce.unwrap_or(0),
+        payload.verifiable,
+        state.game_config.apex_number_max,
+    )
+    .await
+    .map_err(|e| internal_error(&format!("Failed to create game session: {}", e)))?;
+    session.rounds_remaining = payload.rounds;
+    let blinder_result = match payload.option {
+        GameOption::Blinder => Some(
+            session
+                .get_blinder_result(max_payout_f64(state), &state.game_config)
+                .map_err(|e| bad_request(&e.to_string()))?,
+        ),
+        GameOption::NonBlinder | GameOption::Hidden => None,
+    };
+
+    // Deduct bet amount atomically so concurrent bets can't overdraw the balance
+    let _updated_user = state.store.try_deduct_in_game_balance(&user.user_id, &bet_amount).await
+        .map_err(|e| internal_error(&format!("Failed to deduct in-game balance: {}", e)))?
+        .ok_or_else(|| bad_request("Insufficient in-game balance").with_code(ApiErrorCode::InsufficientBalance))?;
+
+    // Until the bet is on the ledger and an open session saved, a failure hands the stake back
+    let taken = async {
+        session.bonus_wagered = state.store.apply_bonus_wager(&user.user_id, &bet_amount).await
+            .map_err(|e| internal_error(&format!("Failed to count bet towards bonus wagering: {}", e)))?;
+        // The stake is a loss whatever the outcome, a blinder win is credited on its own below
+        let bet_transaction = GameTransaction {
+            id: String::new(),
+            user_id: user.user_id.clone(),
+            transaction_type: "game_loss".to_string(),
+            amount: bet_amount.clone(),
+            game_type: Some("apex".to_string()),
+            game_session_id: Some(session.id.clone()),
+            description: Some(match payload.option {
+                GameOption::Blinder => "Apex blinder game bet",
+                GameOption::Hidden => "Apex hidden game bet",
+                GameOption::NonBlinder => "Apex non-blinder game bet",
+            }.to_string()),
+            created_at: None,
+        };
+        state.store.create_transaction(&bet_transaction).await
+            .map_err(|e| internal_error(&format!("Failed to record bet transaction: {}", e)))?;
 
-    // Check if user has enough in-game balance
-    let bet_amount = BigDecimal::from_str(&payload.amount.to_string())
-        .map_err(|_| bad_request("Invalid amount format"))?;
-    if user.in_game_balance < bet_amount {
-        return Err(bad_request("Insufficient in-game balance"));
+        // Blinder games settle immediately, only sessions awaiting a choice are kept
+        if session.status == SessionStatus::Active {
+            state
+                .save_session(
+                    &Service::Apex,
+                    &user.user_id,
+                    &session.id,
+                    to_value(&session).map_err(|_| internal_error("Serialization error"))?,
+                )
+                .await
+                .map_err(|e| internal_error(&format!("Failed to save session: {}", e)))?;
+        }
+        Ok::<_, Response<()>>(())
+    }
+    .await;
+    if let Err(e) = taken {
+        state
+            .refund_failed_start(&Service::Apex, &user.user_id, &session.id, &bet_amount, &session.bonus_wagered)
+            .await;
+        return Err(e.into());
     }
 
-    // Deduct bet amount from user's in-game balance
-    let _updated_user = state.store.adjust_in_game_balance(&user.user_id, &(-bet_amount.clone())).await
-        .map_err(|e| internal_error(&format!("Failed to deduct in-game balance: {}", e)))?;
-    let mut session = GameSession::new(payload.amount, payload.option.clone()).await
-        .map_err(|e| internal_error(&format!("Failed to create game session: {}", e)))?;
     let (
         payout_high,
         prob_high,
@@ -250,20 +615,17 @@ async fn start_game(
         prob_low,
         payout_equal,
         prob_equal,
+        available_choices,
         payout_percentage,
-        blinder_suit,
-    ) = match payload.option {
-        GameOption::Blinder => {
-            let blinder_result = session
-                .get_blinder_result()
-                .map_err(|e| bad_request(&e.to_string()))?;
-            let probability = 0.45; // 45% win probability
-            let payout_percentage = (1.0 - 0.01) / probability;
-            
+    ) = match &blinder_result {
+        Some(blinder_result) => {
+            let payout_percentage = state.game_config.payout_multiplier(state.game_config.blinder_win_prob);
+
             // Handle blinder result immediately since it's auto-resolved
             if blinder_result.won && blinder_result.payout > 0.0 {
-                let payout_amount = BigDecimal::from_str(&blinder_result.payout.to_string())
-                    .map_err(|_| internal_error("Invalid payout amount"))?;
+                let payout_amount = Amount::try_from(blinder_result.payout)
+                    .map_err(|_| internal_error("Invalid payout amount"))?
+                    .into_inner();
                 let _updated_user = state.store.adjust_in_game_balance(&user.user_id, &payout_amount).await
                     .map_err(|e| internal_error(&format!("Failed to add winnings: {}", e)))?;
 
@@ -275,76 +637,51 @@ async fn start_game(
                     amount: payout_amount,
                     game_type: Some("apex".to_string()),
                     game_session_id: Some(session.id.clone()),
-                    description: Some(format!("Apex blinder win - {} payout", blinder_result.payout)),
+                    description: Some("Apex blinder game win".to_string()),
                     created_at: None,
                 };
                 let _win_recorded = state.store.create_transaction(&win_transaction).await
                     .map_err(|e| internal_error(&format!("Failed to record win transaction: {}", e)))?;
             }
-
-            // Record initial bet transaction
-            let bet_transaction = GameTransaction {
-                id: String::new(),
-                user_id: user.user_id.clone(),
-                transaction_type: if blinder_result.won { "game_win" } else { "game_loss" }.to_string(),
-                amount: bet_amount.clone(),
-                game_type: Some("apex".to_string()),
-                game_session_id: Some(session.id.clone()),
-                description: Some("Apex blinder game bet".to_string()),
-                created_at: None,
-            };
-            let _bet_recorded = state.store.create_transaction(&bet_transaction).await
-                .map_err(|e| internal_error(&format!("Failed to record bet transaction: {}", e)))?;
-
-            (
+            record_apex_round(
+                state,
+                &session,
+                1,
                 None,
-                None,
-                None,
-                None,
-                None,
-                None,
-                Some(payout_percentage),
-                Some(blinder_result),
+                session.system_number,
+                session.user_number.unwrap_or_default(),
+                blinder_result.won,
+                blinder_result.payout,
             )
+            .await?;
+            let outcome = if blinder_result.won { &state.metrics.games_won } else { &state.metrics.games_lost };
+            outcome.with_label_values(&["apex"]).inc();
+
+            (None, None, None, None, None, None, None, Some(payout_percentage))
         }
-        GameOption::NonBlinder => {
-            let high_prob = (9 - session.system_number) as f64 / 10.0;
-            let low_prob = session.system_number as f64 / 10.0;
-            let equal_prob = 1.0 / 10.0;
-            let high_payout = if high_prob > 0.0 {
-                (1.0 - 0.01) / high_prob
-            } else {
-                0.0
-            };
-            let low_payout = if low_prob > 0.0 {
-                (1.0 - 0.01) / low_prob
-            } else {
-                0.0
-            };
-            let equal_payout = (1.0 - 0.01) / equal_prob;
-
-            // Record initial bet transaction for non-blinder (will be resolved when choice is made)
-            let bet_transaction = GameTransaction {
-                id: String::new(),
-                user_id: user.user_id.clone(),
-                transaction_type: "game_loss".to_string(), // Initially treat as loss, will add win if they win
-                amount: bet_amount.clone(),
-                game_type: Some("apex".to_string()),
-                game_session_id: Some(session.id.clone()),
-                description: Some("Apex non-blinder game bet".to_string()),
-                created_at: None,
+        None => {
+            // Odds follow from the system number, so hidden games only learn them on the choice.
+            // Choices that can't win aren't quoted at all
+            let available = session.visible_system_number().map(|_| session.available_choices());
+            let odds = |choice: Choice| match &available {
+                Some(choices) if choices.contains(&choice) => {
+                    let (probability, payout) = session.get_choice_info(&choice, &state.game_config);
+                    (Some(probability), Some(payout))
+                }
+                _ => (None, None),
             };
-            let _bet_recorded = state.store.create_transaction(&bet_transaction).await
-                .map_err(|e| internal_error(&format!("Failed to record bet transaction: {}", e)))?;
+            let (high_prob, high_payout) = odds(Choice::High);
+            let (low_prob, low_payout) = odds(Choice::Low);
+            let (equal_prob, equal_payout) = odds(Choice::Equal);
 
             (
-                Some(high_payout),
-                Some(high_prob),
-                Some(low_payout),
-                Some(low_prob),
-                Some(equal_payout),
-                Some(equal_prob),
-                None,
+                high_payout,
+                high_prob,
+                low_payout,
+                low_prob,
+                equal_payout,
+                equal_prob,
+                available,
                 None,
             )
         }
@@ -353,7 +690,7 @@ async fn start_game(
         id: session.id.clone(),
         amount: payload.amount,
         option: payload.option,
-        system_number: session.system_number,
+        system_number: session.visible_system_number(),
         user_number: session.user_number,
         payout_high,
         probability_high: prob_high,
@@ -361,60 +698,102 @@ async fn start_game(
         probability_low: prob_low,
         payout_equal,
         probability_equal: prob_equal,
+        available_choices,
         payout_percentage,
-        blinder_suit,
+        blinder_suit: blinder_result,
+        server_seed_hash: session.server_seed_hash.clone(),
+        client_seed: session.client_seed.clone(),
+        nonce: session.nonce,
+        server_seed: (session.status == SessionStatus::Ended).then(|| session.server_seed.clone()),
+        rounds: payload.rounds,
         session_status: session.status.clone(),
     };
-    let service_state = match state.sessions.get(&Service::Apex).await {
-        Some(cache) => cache,
-        None => {
-            let cache = new_moka_cache(SESSION_TTL);
-            state.sessions.insert(Service::Apex, cache.clone()).await;
-            cache
-        }
-    };
-    service_state
-        .insert(
-            session.id.clone(),
-            to_value(&session).map_err(|_| internal_error("Serialization error"))?,
-        )
-        .await;
-    Ok(Response::ok(response))
+    state.metrics.games_started.with_label_values(&["apex"]).inc();
+    Ok(response)
 }
 
-async fn make_choice(
+async fn start_game(
     State(state): State<Arc<AppState>>,
-    Extension(user_addr): Extension<String>,
-    Json(payload): Json<ChooseRequest>,
-) -> ApiResult<ChooseResponse> {
-    // Get user from database
-    let user = state.store.get_user_by_wallet_addr(&user_addr).await
+    Extension(user_id): Extension<String>,
+    payload: Result<Json<serde_json::Value>, JsonRejection>,
+) -> CodedResult<StartGameResponse> {
+    let payload: StartGameRequest = bet_request(payload)?;
+    // The auth middleware hands over the token's subject, a user id
+    let user = state.store.get_user_by_id(&user_id).await
         .map_err(|e| internal_error(&format!("Database error: {}", e)))?
-        .ok_or_else(|| bad_request("User not found"))?;
+        .filter(|user| user.deleted_at.is_none())
+        .ok_or_else(|| bad_request("User not found").with_code(ApiErrorCode::UserNotFound))?;
 
-    let service_state = state
-        .sessions
-        .get(&Service::Apex)
-        .await
-        .ok_or(bad_request("Session not found"))?;
-    let mut session: GameSession = service_state
-        .get(&payload.id)
+    let response = resolve_start(&state, &user, payload).await?;
+    Ok(Response::ok(response))
+}
+
+// Shared core of the apex choice routes: plays the session's current round and keeps it
+// for the next, until the last round ends the match and the combined payout is credited
+pub async fn resolve_choice(
+    state: &AppState,
+    user: &User,
+    payload: ChooseRequest,
+) -> Result<ChooseResponse, ApiError> {
+    let _guard = state.lock_session(&payload.id).await;
+    let mut session: GameSession = state
+        .load_session(&Service::Apex, &user.user_id, &payload.id)
         .await
-        .and_then(|v| serde_json::from_value(v.clone()).ok())
-        .ok_or(bad_request("Session not found"))?;
-    
+        .map_err(|e| internal_error(&format!("Database error: {}", e)))?
+        .and_then(|v| serde_json::from_value(v).ok())
+        .ok_or_else(|| bad_request("Session not found").with_code(ApiErrorCode::SessionNotFound))?;
+
     let response = session
-        .make_choice(payload.choice).await
+        .make_choice(payload.choice, max_payout_f64(state), &state.game_config).await
         .map_err(|e| bad_request(&e.to_string()))?;
-    
+
+    if response.session_status == SessionStatus::Active {
+        state
+            .save_session(
+                &Service::Apex,
+                &user.user_id,
+                &session.id,
+                to_value(&session).map_err(|_| internal_error("Serialization error"))?,
+            )
+            .await
+            .map_err(|e| internal_error(&format!("Failed to save session: {}", e)))?;
+        state
+            .publish_game_update(
+                &session.id,
+                to_value(&response).map_err(|_| internal_error("Serialization error"))?,
+                false,
+            )
+            .await;
+        return Ok(response);
+    }
+
+    // Ending the session is the claim on its payout, so a duplicate request can't credit it twice
+    let claimed = state
+        .remove_session(&Service::Apex, &user.user_id, &session.id)
+        .await
+        .map_err(|e| internal_error(&format!("Failed to remove session: {}", e)))?;
+    if !claimed {
+        return Err(bad_request("Session is not active").with_code(ApiErrorCode::SessionNotActive));
+    }
+
     // Handle winnings
-    if response.won && response.payout > 0.0 {
-        let payout_amount = BigDecimal::from_str(&response.payout.to_string())
-            .map_err(|_| internal_error("Invalid payout amount"))?;
+    if response.total_payout > 0.0 {
+        let payout_amount = Amount::try_from(response.total_payout)
+            .map_err(|_| internal_error("Invalid payout amount"))?
+            .into_inner();
         let _updated_user = state.store.adjust_in_game_balance(&user.user_id, &payout_amount).await
             .map_err(|e| internal_error(&format!("Failed to add winnings: {}", e)))?;
 
         // Record win transaction
+        let description = match response.round_results.as_slice() {
+            [round] => format!("Apex choice win - {} payout from choice {:?}", round.payout, round.choice),
+            rounds => format!(
+                "Apex match win - {} payout from {} of {} rounds",
+                response.total_payout,
+                rounds.iter().filter(|r| r.won).count(),
+                rounds.len()
+            ),
+        };
         let win_transaction = GameTransaction {
             id: String::new(),
             user_id: user.user_id.clone(),
@@ -422,19 +801,51 @@ async fn make_choice(
             amount: payout_amount,
             game_type: Some("apex".to_string()),
             game_session_id: Some(session.id.clone()),
-            description: Some(format!("Apex choice win - {} payout from choice {:?}", response.payout, response.choice)),
+            description: Some(description),
             created_at: None,
         };
         let _win_recorded = state.store.create_transaction(&win_transaction).await
             .map_err(|e| internal_error(&format!("Failed to record win transaction: {}", e)))?;
     }
+    for (index, round) in response.round_results.iter().enumerate() {
+        record_apex_round(
+            state,
+            &session,
+            index as u32 + 1,
+            Some(format!("{:?}", round.choice)),
+            round.system_number,
+            round.user_number,
+            round.won,
+            round.payout,
+        )
+        .await?;
+    }
+
+    let outcome = if response.total_payout > 0.0 { &state.metrics.games_won } else { &state.metrics.games_lost };
+    outcome.with_label_values(&["apex"]).inc();
 
-    service_state
-        .insert(
-            session.id.clone(),
-            to_value(&session).map_err(|_| internal_error("Serialization error"))?,
+    state
+        .publish_game_update(
+            &session.id,
+            to_value(&response).map_err(|_| internal_error("Serialization error"))?,
+            true,
         )
         .await;
+    Ok(response)
+}
+
+async fn make_choice(
+    State(state): State<Arc<AppState>>,
+    Extension(user_id): Extension<String>,
+    Json(payload): Json<ChooseRequest>,
+) -> CodedResult<ChooseResponse> {
+    // The auth middleware hands over the token's subject, a user id
+    let user = state.store.get_user_by_id(&user_id).await
+        .map_err(|e| internal_error(&format!("Database error: {}", e)))?
+        .filter(|user| user.deleted_at.is_none())
+        .ok_or_else(|| bad_request("User not found").with_code(ApiErrorCode::UserNotFound))?;
+
+    let response = resolve_choice(&state, &user, payload).await?;
     Ok(Response::ok(response))
 }
 
@@ -444,3 +855,537 @@ pub fn router(state: Arc<AppState>) -> Router {
         .route("/choose", post(make_choice))
         .with_state(state)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn start_request(option: GameOption) -> StartGameRequest {
+        StartGameRequest {
+            amount: "1".parse().unwrap(),
+            option,
+            client_seed: None,
+            nonce: None,
+            verifiable: false,
+            rounds: 1,
+        }
+    }
+
+    fn blinder_session(amount: f64, system_number: u32, user_number: u32) -> GameSession {
+        GameSession {
+            id: Uuid::new_v4().to_string(),
+            user_id: "user".to_string(),
+            amount,
+            option: GameOption::Blinder,
+            system_number,
+            user_number: Some(user_number),
+            server_seed: generate_seed(),
+            server_seed_hash: String::new(),
+            client_seed: String::new(),
+            nonce: 0,
+            verifiable: false,
+            number_max: DEFAULT_APEX_NUMBER_MAX,
+            status: SessionStatus::Active,
+            bonus_wagered: BigDecimal::from(0),
+            round_results: Vec::new(),
+            rounds_remaining: 1,
+            version: 0,
+        }
+    }
+
+    #[test]
+    fn test_blinder_payout_clamped_to_max_payout() {
+        let mut session = blinder_session(100.0, 2, 7);
+        let result = session.get_blinder_result(50.0, &GameConfig::default()).unwrap();
+        assert!(result.won);
+        assert_eq!(result.uncapped_payout, 100.0 * ((1.0 - 0.01) / 0.45));
+        assert_eq!(result.payout, 50.0);
+    }
+
+    #[test]
+    fn test_blinder_payout_below_cap_is_unchanged() {
+        let mut session = blinder_session(1.0, 2, 7);
+        let result = session.get_blinder_result(50.0, &GameConfig::default()).unwrap();
+        assert_eq!(result.payout, result.uncapped_payout);
+    }
+
+    #[test]
+    fn test_house_edge_scales_payouts() {
+        let mut session = blinder_session(1.0, 4, 7);
+        let default = GameConfig::default();
+        let greedy = GameConfig {
+            house_edge: 0.05,
+            ..GameConfig::default()
+        };
+
+        let (probability, payout) = session.get_choice_info(&Choice::High, &default);
+        assert_eq!(probability, 0.5);
+        assert_eq!(payout, 0.99 / 0.5);
+        assert_eq!(session.get_choice_info(&Choice::High, &greedy).1, 0.95 / 0.5);
+
+        let result = session.get_blinder_result(f64::MAX, &greedy).unwrap();
+        assert_eq!(result.payout, 0.95 / 0.45);
+    }
+
+    #[test]
+    fn test_out_of_range_random_number_is_reduced() {
+        let mut session = blinder_session(1.0, 0, 0);
+        session.option = GameOption::NonBlinder;
+        session.user_number = None;
+        session.use_random_number(15);
+        assert_eq!(session.system_number, 5);
+
+        for choice in [Choice::High, Choice::Low, Choice::Equal] {
+            let (probability, payout) = session.get_choice_info(&choice, &GameConfig::default());
+            assert!((0.0..=1.0).contains(&probability));
+            assert!(payout >= 0.0);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_wider_number_range_recomputes_odds() {
+        let mut session = blinder_session(1.0, 40, 0);
+        session.option = GameOption::NonBlinder;
+        session.number_max = 99;
+        let config = GameConfig { apex_number_max: 99, ..GameConfig::default() };
+
+        let (high, high_payout) = session.get_choice_info(&Choice::High, &config);
+        let (low, low_payout) = session.get_choice_info(&Choice::Low, &config);
+        let (equal, equal_payout) = session.get_choice_info(&Choice::Equal, &config);
+        assert_eq!(high, 59.0 / 100.0);
+        assert_eq!(low, 40.0 / 100.0);
+        assert_eq!(equal, 1.0 / 100.0);
+        assert!((high + low + equal - 1.0).abs() < 1e-12);
+        assert_eq!(high_payout, 0.99 / (59.0 / 100.0));
+        assert_eq!(low_payout, 0.99 / (40.0 / 100.0));
+        assert_eq!(equal_payout, 0.99 / (1.0 / 100.0));
+
+        // Drawn numbers cover the whole range rather than single digits
+        let numbers: Vec<u32> = (0..200).map(|nonce| derive_apex_number("server", "client", nonce, 0, 99)).collect();
+        assert!(numbers.iter().all(|&n| n <= 99));
+        assert!(numbers.iter().any(|&n| n > 9));
+
+        session.use_random_number(1234);
+        assert_eq!(session.system_number, 34);
+        let started = GameSession::new(1.0, GameOption::NonBlinder, "user".to_string(), generate_seed(), 0, false, 99)
+            .await
+            .unwrap();
+        assert_eq!(started.number_max, 99);
+    }
+
+    #[tokio::test]
+    async fn test_dead_choices_are_neither_offered_nor_playable() {
+        let config = GameConfig::default();
+        for (system_number, dead, live) in [(9, Choice::High, Choice::Low), (0, Choice::Low, Choice::High)] {
+            let mut session = blinder_session(1.0, system_number, 0);
+            session.option = GameOption::NonBlinder;
+            session.user_number = None;
+
+            assert_eq!(session.get_choice_info(&dead, &config).0, 0.0);
+            assert_eq!(session.available_choices(), vec![live.clone(), Choice::Equal]);
+            assert_eq!(session.view().available_choices, Some(session.available_choices()));
+            let probabilities: f64 = [Choice::High, Choice::Low, Choice::Equal]
+                .iter()
+                .map(|choice| session.get_choice_info(choice, &config).0)
+                .sum();
+            assert!((probabilities - 1.0).abs() < 1e-12);
+
+            let err = session.make_choice(dead.clone(), f64::MAX, &config).await.unwrap_err();
+            assert_eq!(err.to_string(), format!("{:?} can't win against a system number of {}", dead, system_number));
+            assert!(session.round_results.is_empty() && session.status == SessionStatus::Active);
+            assert!(session.make_choice(live, f64::MAX, &config).await.is_ok());
+
+            // Choosing blind, a hidden game's player may still pick the dead side and lose
+            let mut hidden = blinder_session(1.0, system_number, 0);
+            hidden.option = GameOption::Hidden;
+            hidden.user_number = None;
+            assert_eq!(hidden.view().available_choices, None);
+            assert!(!hidden.make_choice(dead, f64::MAX, &config).await.unwrap().won);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_drawn_numbers_win_as_often_as_advertised() {
+        const DRAWS: u64 = 20_000;
+        let config = GameConfig::default();
+        let mut session = GameSession::from_seeds(1.0, GameOption::NonBlinder, "user".to_string(), "server".to_string(), "client".to_string(), 0, DEFAULT_APEX_NUMBER_MAX);
+
+        // Tolerances sit around six standard deviations out, so a fair draw doesn't flake
+        for system_number in 0..=session.number_max {
+            session.system_number = system_number;
+            let (high_prob, _) = session.get_choice_info(&Choice::High, &config);
+            let high_wins = (0..DRAWS)
+                .map(|nonce| derive_apex_number("server", "client", nonce, 1, session.number_max))
+                .filter(|&user_number| user_number > system_number)
+                .count();
+            let frequency = high_wins as f64 / DRAWS as f64;
+            assert!((frequency - high_prob).abs() < 0.02, "system number {}: won {} against {}", system_number, frequency, high_prob);
+        }
+
+        // The same holds for the choice made on a seeded session
+        session.system_number = 6;
+        let (high_prob, _) = session.get_choice_info(&Choice::High, &config);
+        let mut high_wins = 0;
+        for nonce in 0..DRAWS {
+            let mut game = GameSession::from_seeds(1.0, GameOption::NonBlinder, "user".to_string(), "server".to_string(), "client".to_string(), nonce, DEFAULT_APEX_NUMBER_MAX);
+            game.system_number = 6;
+            high_wins += game.make_choice(Choice::High, f64::MAX, &config).await.unwrap().won as u64;
+        }
+        assert!((high_wins as f64 / DRAWS as f64 - high_prob).abs() < 0.02);
+
+        // Blinder wins when its user number beats the system number, at the odds theoretical_rtp uses
+        let count = session.number_count() as f64;
+        let blinder_prob = (count - 1.0) / (2.0 * count);
+        let blinder_wins = (0..DRAWS)
+            .filter(|&nonce| {
+                let game = GameSession::from_seeds(1.0, GameOption::Blinder, "user".to_string(), "server".to_string(), "client".to_string(), nonce, DEFAULT_APEX_NUMBER_MAX);
+                game.user_number.unwrap() > game.system_number
+            })
+            .count();
+        assert!((blinder_wins as f64 / DRAWS as f64 - blinder_prob).abs() < 0.02);
+    }
+
+    #[tokio::test]
+    async fn test_identical_seeds_reproduce_numbers() {
+        let seeded = |option| {
+            GameSession::from_seeds(1.0, option, "user".to_string(), "server".to_string(), "client".to_string(), 7, DEFAULT_APEX_NUMBER_MAX)
+        };
+        let (first, second) = (seeded(GameOption::Blinder), seeded(GameOption::Blinder));
+        assert_eq!(first.system_number, second.system_number);
+        assert_eq!(first.user_number, second.user_number);
+        assert_eq!(first.system_number, derive_apex_number("server", "client", 7, 0, DEFAULT_APEX_NUMBER_MAX));
+        assert_eq!(first.user_number, Some(derive_apex_number("server", "client", 7, 1, DEFAULT_APEX_NUMBER_MAX)));
+        assert_eq!(first.server_seed_hash, hash_seed("server"));
+
+        // Non-blinder draws the user number from the same seeds when the choice is made
+        let mut session = seeded(GameOption::NonBlinder);
+        let response = session.make_choice(Choice::Equal, f64::MAX, &GameConfig::default()).await.unwrap();
+        assert_eq!(response.user_number, derive_apex_number("server", "client", 7, 1, DEFAULT_APEX_NUMBER_MAX));
+        assert_eq!(response.server_seed.as_deref(), Some("server"));
+
+        // A different client seed moves at least one of a handful of rounds
+        assert!((0..8).any(|nonce| {
+            derive_apex_number("server", "client", nonce, 0, DEFAULT_APEX_NUMBER_MAX)
+                != derive_apex_number("server", "other", nonce, 0, DEFAULT_APEX_NUMBER_MAX)
+        }));
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_random_server_falls_back_unless_strict() {
+        // Nothing listens on port 1
+        let unreachable = "http://127.0.0.1:1";
+        let mut session = GameSession::from_seeds(1.0, GameOption::NonBlinder, "user".to_string(), generate_seed(), "client".to_string(), 0, DEFAULT_APEX_NUMBER_MAX);
+        let number = get_random_below_with_fallback(unreachable, false, session.number_count()).await.unwrap();
+        session.use_random_number(number);
+        assert!(session.system_number <= session.number_max);
+
+        assert!(get_random_below_with_fallback(unreachable, true, session.number_count()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_verifiable_draws_cover_a_wide_range_from_single_digits() {
+        use axum::{Json, Router as MockRouter, routing::get};
+
+        // Random server answering 0-9 like the real one
+        let app = MockRouter::new().route(
+            "/random",
+            get(|| async {
+                let digit = rand::thread_rng().gen_range(0..10);
+                Json(serde_json::json!({ "success": true, "randomNumber": digit }))
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        let url = format!("http://{}", addr);
+
+        let mut counts = [0u32; 100];
+        for _ in 0..2000 {
+            let number = get_random_below_with_fallback(&url, true, 100).await.unwrap();
+            counts[number as usize] += 1;
+        }
+        // 20 draws expected per number; a single-digit modulo would never pass 9
+        assert!(counts[10..].iter().sum::<u32>() > 1600, "{:?}", counts);
+        assert!(counts.iter().all(|&count| count < 60), "{:?}", counts);
+    }
+
+    #[test]
+    fn test_theoretical_rtp_reflects_house_edge() {
+        let rtp = theoretical_rtp(&GameConfig::default());
+        assert!((rtp.blinder - 0.99).abs() < 1e-9);
+        assert!((rtp.non_blinder - 0.99).abs() < 1e-9);
+        // High and low can't win on 1 in 10 system numbers
+        assert!((rtp.hidden - 0.99 * 28.0 / 30.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_abandoned_refund_only_while_number_is_hidden() {
+        let seeded = |option| {
+            GameSession::from_seeds(1.5, option, "user".to_string(), "server".to_string(), "client".to_string(), 7, DEFAULT_APEX_NUMBER_MAX)
+        };
+        assert_eq!(seeded(GameOption::Hidden).abandoned_refund(), Some(BigDecimal::from_str("1.5").unwrap()));
+        assert_eq!(seeded(GameOption::NonBlinder).abandoned_refund(), None);
+        let mut ended = seeded(GameOption::Hidden);
+        ended.status = SessionStatus::Ended;
+        assert_eq!(ended.abandoned_refund(), None);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_start_handles_both_options() {
+        let state = AppState::default().await;
+        let user = state.store.create_funded_user(10).await.unwrap();
+
+        // Blinder settles on the spot: one recorded round and nothing left to choose
+        let Ok(blinder) = resolve_start(&state, &user, start_request(GameOption::Blinder)).await else {
+            panic!("blinder start failed");
+        };
+        assert_eq!(blinder.session_status, SessionStatus::Ended);
+        assert!(blinder.blinder_suit.is_some() && blinder.payout_percentage.is_some());
+        assert_eq!(blinder.server_seed.as_deref().map(hash_seed), Some(blinder.server_seed_hash.clone()));
+        assert!(state.load_session(&Service::Apex, &user.user_id, &blinder.id).await.unwrap().is_none());
+        let rounds = state.store.get_apex_rounds(&user.user_id).await.unwrap();
+        assert_eq!(rounds.len(), 1);
+        assert_eq!(rounds[0].session_id, blinder.id);
+
+        // Non-blinder quotes every choice and keeps the session for make_choice
+        let Ok(open) = resolve_start(&state, &user, start_request(GameOption::NonBlinder)).await else {
+            panic!("non-blinder start failed");
+        };
+        assert_eq!(open.session_status, SessionStatus::Active);
+        assert!(open.server_seed.is_none() && open.blinder_suit.is_none());
+        assert_eq!(open.probability_equal, Some(0.1));
+        // High and low are quoted unless the system number leaves them no way to win
+        let available = open.available_choices.clone().unwrap();
+        assert_eq!(open.payout_high.is_some(), available.contains(&Choice::High));
+        assert_eq!(open.payout_low.is_some(), available.contains(&Choice::Low));
+        assert!(state.load_session(&Service::Apex, &user.user_id, &open.id).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_won_blinder_game_keeps_the_ledger_balanced() {
+        let state = AppState::default().await;
+        let user = state.store.create_funded_user(100).await.unwrap();
+        let unrecorded = state.store.reconcile_user(&user.user_id).await.unwrap().unwrap().discrepancy;
+
+        // Play until a blinder game wins; each one must leave the ledger in step with the balance
+        let mut won = false;
+        for _ in 0..50 {
+            let Ok(game) = resolve_start(&state, &user, start_request(GameOption::Blinder)).await else {
+                panic!("blinder start failed");
+            };
+            let reconciliation = state.store.reconcile_user(&user.user_id).await.unwrap().unwrap();
+            assert_eq!(reconciliation.discrepancy, unrecorded);
+            won = game.blinder_suit.as_ref().is_some_and(|suit| suit.won);
+            if won {
+                break;
+            }
+        }
+        assert!(won);
+    }
+
+    #[tokio::test]
+    async fn test_hidden_game_reveals_system_number_on_choice() {
+        let state = AppState::default().await;
+        let user = state.store.create_funded_user(10).await.unwrap();
+
+        // Neither the number nor the odds it implies are given out at the start
+        let Ok(start) = resolve_start(&state, &user, start_request(GameOption::Hidden)).await else {
+            panic!("hidden start failed");
+        };
+        assert_eq!(start.session_status, SessionStatus::Active);
+        assert!(start.system_number.is_none() && start.user_number.is_none());
+        assert!(start.payout_high.is_none() && start.probability_equal.is_none());
+        let stored = state.load_session(&Service::Apex, &user.user_id, &start.id).await.unwrap().unwrap();
+        let mut session: GameSession = serde_json::from_value(stored).unwrap();
+        assert!(session.view().system_number.is_none());
+
+        let response = session.make_choice(Choice::Low, f64::MAX, &state.game_config).await.unwrap();
+        assert_eq!(response.session_status, SessionStatus::Ended);
+        assert_eq!(session.view().system_number, Some(response.system_number));
+        let (_, payout_multiplier) = session.get_choice_info(&Choice::Low, &state.game_config);
+        assert_eq!(response.payout_multiplier, payout_multiplier);
+        if response.won {
+            assert_eq!(response.uncapped_payout, 1.0 * payout_multiplier);
+        }
+
+        // The revealed seed matches the commitment and reproduces both numbers
+        let server_seed = response.server_seed.clone().unwrap();
+        assert_eq!(hash_seed(&server_seed), start.server_seed_hash);
+        let number_max = state.game_config.apex_number_max;
+        assert_eq!(
+            response.system_number,
+            derive_apex_number(&server_seed, &start.client_seed, start.nonce, 0, number_max)
+        );
+        assert_eq!(
+            response.user_number,
+            derive_apex_number(&server_seed, &start.client_seed, start.nonce, 1, number_max)
+        );
+        assert!(record_apex_round(&state, &session, 1, Some("Low".to_string()), response.system_number, response.user_number, response.won, response.payout).await.is_ok());
+        assert_eq!(state.store.get_apex_rounds(&user.user_id).await.unwrap()[0].option, "Hidden");
+    }
+
+    #[tokio::test]
+    async fn test_three_round_match_pays_out_at_the_end() {
+        let state = AppState::default().await;
+        let user = state.store.create_funded_user(10).await.unwrap();
+        let balance = || async { state.store.get_user_by_id(&user.user_id).await.unwrap().unwrap().in_game_balance };
+
+        // Blinder settles on the spot, so it can't be played over rounds
+        let blinder = StartGameRequest { rounds: 3, ..start_request(GameOption::Blinder) };
+        assert!(resolve_start(&state, &user, blinder).await.is_err());
+        let endless = StartGameRequest { rounds: MAX_APEX_ROUNDS + 1, ..start_request(GameOption::NonBlinder) };
+        assert!(resolve_start(&state, &user, endless).await.is_err());
+        assert_eq!(balance().await, BigDecimal::from(10));
+
+        let request = StartGameRequest { rounds: 3, ..start_request(GameOption::NonBlinder) };
+        let Ok(start) = resolve_start(&state, &user, request).await else {
+            panic!("match start failed");
+        };
+        assert_eq!(start.rounds, 3);
+        assert_eq!(balance().await, BigDecimal::from(7));
+        let stored = state.load_session(&Service::Apex, &user.user_id, &start.id).await.unwrap().unwrap();
+        let session: GameSession = serde_json::from_value(stored).unwrap();
+
+        // Win the first and last rounds and lose the middle one
+        let mut total = 0.0;
+        for (round, win) in [true, false, true].into_iter().enumerate() {
+            let draw = |index| derive_apex_number(&session.server_seed, &session.client_seed, session.nonce, index, session.number_max);
+            let (system_number, user_number) = (draw(2 * round as u64), draw(2 * round as u64 + 1));
+            let choice = match (user_number.cmp(&system_number), win) {
+                (std::cmp::Ordering::Greater, true) => Choice::High,
+                (std::cmp::Ordering::Less, true) => Choice::Low,
+                (std::cmp::Ordering::Equal, true) | (std::cmp::Ordering::Greater | std::cmp::Ordering::Less, false) => Choice::Equal,
+                // Lose on a tie with whichever of high and low is still on offer
+                (std::cmp::Ordering::Equal, false) if system_number < session.number_max => Choice::High,
+                (std::cmp::Ordering::Equal, false) => Choice::Low,
+            };
+            let payload = ChooseRequest { id: start.id.clone(), choice };
+            let Ok(response) = resolve_choice(&state, &user, payload).await else {
+                panic!("round {} failed", round + 1);
+            };
+            assert_eq!(response.round as usize, round + 1);
+            assert_eq!((response.system_number, response.user_number), (system_number, user_number));
+            assert_eq!(response.won, win);
+            assert_eq!(response.rounds_remaining as usize, 2 - round);
+            assert_eq!(response.round_results.len(), round + 1);
+            total += response.payout;
+            assert_eq!(response.total_payout, total);
+
+            if response.rounds_remaining > 0 {
+                // Nothing is credited or revealed mid-match, but the next round can be played
+                assert_eq!(response.session_status, SessionStatus::Active);
+                assert!(response.server_seed.is_none());
+                assert_eq!(balance().await, BigDecimal::from(7));
+                assert_eq!(response.next_system_number, Some(draw(2 * (round as u64 + 1))));
+                assert!(response.available_choices.as_ref().is_some_and(|choices| choices.contains(&Choice::Equal)));
+            } else {
+                assert_eq!(response.session_status, SessionStatus::Ended);
+                assert_eq!(response.server_seed.as_deref(), Some(session.server_seed.as_str()));
+                assert!(response.next_system_number.is_none() && response.available_choices.is_none());
+            }
+        }
+
+        assert!(total > 0.0);
+        let payout = Amount::try_from(total).unwrap().into_inner();
+        assert_eq!(balance().await, BigDecimal::from(7) + payout);
+        assert!(state.load_session(&Service::Apex, &user.user_id, &start.id).await.unwrap().is_none());
+        let rounds = state.store.get_apex_rounds(&user.user_id).await.unwrap();
+        let mut played: Vec<_> = rounds.iter().filter(|r| r.session_id == start.id).map(|r| r.round).collect();
+        played.sort();
+        assert_eq!(played, vec![1, 2, 3]);
+        let wins = state.store.get_user_transactions(&user.user_id, None).await.unwrap();
+        assert_eq!(wins.iter().filter(|t| t.transaction_type == "game_win").count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_match_stake_and_winnings_stay_within_limits() {
+        let mut state = AppState::default().await;
+        state.bet_limits.max = BigDecimal::from(2);
+        let user = state.store.create_funded_user(10).await.unwrap();
+
+        // Each round's bet fits the limit, but three of them staked together don't
+        let request = StartGameRequest { rounds: 3, ..start_request(GameOption::NonBlinder) };
+        assert!(resolve_start(&state, &user, request).await.is_err());
+        let balance = state.store.get_user_by_id(&user.user_id).await.unwrap().unwrap().in_game_balance;
+        assert_eq!(balance, BigDecimal::from(10));
+
+        // Winning every round still pays no more than the max payout in total
+        let mut session = GameSession::from_seeds(1.0, GameOption::NonBlinder, "user".to_string(), generate_seed(), "client".to_string(), 0, DEFAULT_APEX_NUMBER_MAX);
+        session.rounds_remaining = 3;
+        let mut total_payout = 0.0;
+        for round in 0..3 {
+            let user_number = derive_apex_number(&session.server_seed, &session.client_seed, session.nonce, 2 * round + 1, session.number_max);
+            let choice = match user_number.cmp(&session.system_number) {
+                std::cmp::Ordering::Greater => Choice::High,
+                std::cmp::Ordering::Less => Choice::Low,
+                std::cmp::Ordering::Equal => Choice::Equal,
+            };
+            let response = session.make_choice(choice, 1.5, &state.game_config).await.unwrap();
+            assert!(response.won);
+            total_payout = response.total_payout;
+        }
+        assert_eq!(total_payout, 1.5);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_choices_pay_out_once() {
+        // Two instances sharing the database, as behind a load balancer
+        let state = Arc::new(AppState::default().await);
+        let other = Arc::new(AppState::default().await);
+        let user = state.store.create_funded_user(10).await.unwrap();
+        let mut session = GameSession::from_seeds(1.0, GameOption::NonBlinder, user.user_id.clone(), generate_seed(), "client".to_string(), 0, DEFAULT_APEX_NUMBER_MAX);
+        // Pick whichever choice wins against the seeded numbers
+        let user_number = derive_apex_number(&session.server_seed, &session.client_seed, 0, 1, DEFAULT_APEX_NUMBER_MAX);
+        let choice = match user_number.cmp(&session.system_number) {
+            std::cmp::Ordering::Greater => Choice::High,
+            std::cmp::Ordering::Less => Choice::Low,
+            std::cmp::Ordering::Equal => Choice::Equal,
+        };
+        let (_, multiplier) = session.get_choice_info(&choice, &state.game_config);
+        session.id = Uuid::new_v4().to_string();
+        state
+            .save_session(&Service::Apex, &user.user_id, &session.id, serde_json::to_value(&session).unwrap())
+            .await
+            .unwrap();
+        // Both have the still active session cached, so neither lock nor cache stops a replay
+        assert!(other.load_session(&Service::Apex, &user.user_id, &session.id).await.unwrap().is_some());
+
+        let choose = |state: &Arc<AppState>| {
+            make_choice(
+                State(state.clone()),
+                Extension(user.user_id.clone()),
+                Json(ChooseRequest { id: session.id.clone(), choice: choice.clone() }),
+            )
+        };
+        let (first, second) = tokio::join!(choose(&state), choose(&other));
+        let won: Vec<_> = [first, second].into_iter().filter_map(Result::ok).collect();
+        assert_eq!(won.len(), 1);
+
+        let wins = state.store.get_user_transactions(&user.user_id, None).await.unwrap();
+        assert_eq!(wins.iter().filter(|t| t.transaction_type == "game_win").count(), 1);
+        let updated = state.store.get_user_by_id(&user.user_id).await.unwrap().unwrap();
+        let payout = Amount::try_from(multiplier).unwrap().into_inner();
+        assert_eq!(updated.in_game_balance, BigDecimal::from(10) + payout);
+    }
+
+    #[tokio::test]
+    async fn test_resolved_apex_round_is_recorded() {
+        let state = AppState::default().await;
+        let user = state.store.create_funded_user(10).await.unwrap();
+        let mut session = blinder_session(1.0, 2, 7);
+        session.user_id = user.user_id.clone();
+        let result = session.get_blinder_result(max_payout_f64(&state), &state.game_config).unwrap();
+        assert!(record_apex_round(&state, &session, 1, None, 2, 7, result.won, result.payout).await.is_ok());
+
+        let rounds = state.store.get_apex_rounds(&user.user_id).await.unwrap();
+        assert_eq!(rounds.len(), 1);
+        let round = &rounds[0];
+        assert_eq!(round.session_id, session.id);
+        assert_eq!(round.option, "Blinder");
+        assert_eq!((round.system_number, round.user_number), (2, 7));
+        assert!(round.won);
+        assert!(round.choice.is_none());
+        assert_eq!(round.payout, Amount::try_from(result.payout).unwrap().into_inner());
+    }
+}
@@ -1,14 +1,19 @@
 use alloy::transports::BoxFuture;
+use axum::async_trait;
 use axum::body::Body;
-use axum::extract::Request;
-use axum::http::{self, HeaderMap, StatusCode};
+use axum::extract::{FromRequestParts, Request};
+use axum::http::{self, HeaderMap, StatusCode, request::Parts};
 use axum::response::Response;
-use jsonwebtoken::{DecodingKey, Validation, decode};
+use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation, decode, encode};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
 use std::task::{Context, Poll};
 use std::time::SystemTime;
 use tower::{Layer, Service};
 
-use crate::auth::{AuthError, Claims};
+use crate::auth::{AuthError, Claims, RevokedTokens};
+use crate::primitives::{ApiError, ApiErrorCode, WithErrorCode, with_status};
+use crate::store::{Store, User};
 
 /// Constant representing the admin address for privileged access
 pub const ADMIN_ADDRESS: &str = "Admin";
@@ -18,6 +23,7 @@ pub const ADMIN_ADDRESS: &str = "Admin";
 pub struct AuthLayer {
     pub expected_secret: String, // Expected server secret for admin authentication
     pub jwt_secret: String,      // Secret used to validate JWT tokens
+    pub revoked_tokens: Arc<RevokedTokens>, // Token ids invalidated by logout
 }
 
 impl<S> Layer<S> for AuthLayer {
@@ -29,6 +35,7 @@ impl<S> Layer<S> for AuthLayer {
             inner,
             admin_secret: self.expected_secret.clone(),
             jwt_secret: self.jwt_secret.clone(),
+            revoked_tokens: self.revoked_tokens.clone(),
         }
     }
 }
@@ -39,6 +46,7 @@ pub struct AuthMiddleware<S> {
     inner: S,
     admin_secret: String,
     jwt_secret: String,
+    revoked_tokens: Arc<RevokedTokens>,
 }
 
 impl<S> Service<Request> for AuthMiddleware<S>
@@ -59,13 +67,18 @@ where
     fn call(&mut self, mut req: Request) -> Self::Future {
         let admin_secret = self.admin_secret.clone();
         let jwt_secret = self.jwt_secret.clone();
+        let revoked_tokens = self.revoked_tokens.clone();
         let mut inner = self.inner.clone();
 
         Box::pin(async move {
-            match authenticate(req.headers(), &admin_secret, &jwt_secret) {
-                Ok(addr) => {
-                    // Insert authenticated address into request extensions
+            match authenticate(req.headers(), &admin_secret, &jwt_secret, &revoked_tokens) {
+                Ok((addr, claims)) => {
+                    // Insert authenticated address into request extensions, plus the
+                    // token's claims for JWT callers (e.g. so logout can revoke it)
                     req.extensions_mut().insert(addr);
+                    if let Some(claims) = claims {
+                        req.extensions_mut().insert(claims);
+                    }
                     inner.call(req).await
                 }
                 Err(_) => Ok(unauthorized_response()),
@@ -74,21 +87,109 @@ where
     }
 }
 
+/// Extractor for admin-only handlers. Passes callers the auth layer identified by the
+/// server secret and answers everyone else, JWT users included, with a 403
+pub struct Admin;
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Admin {
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let caller = parts.extensions.get::<String>();
+        // A JWT caller always carries its claims, so the marker alone can't be spoofed by a token
+        if caller.is_some_and(|caller| caller == ADMIN_ADDRESS) && parts.extensions.get::<Claims>().is_none() {
+            Ok(Admin)
+        } else {
+            Err(with_status(StatusCode::FORBIDDEN, garden::api::bad_request("Admin access required")))
+        }
+    }
+}
+
+/// Who a request on an authenticated route acts for: the user a token was issued to,
+/// or the admin, who may act for any account
+pub enum Caller {
+    User(Claims),
+    Admin,
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Caller {
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        if let Some(claims) = parts.extensions.get::<Claims>() {
+            return Ok(Caller::User(claims.clone()));
+        }
+        match parts.extensions.get::<String>() {
+            Some(caller) if caller == ADMIN_ADDRESS => Ok(Caller::Admin),
+            // Only reachable on a route mounted without the auth layer
+            _ => Err(unauthorized_response()),
+        }
+    }
+}
+
+impl Caller {
+    /// The account a request acts on. A token acts on its own account, and an address the
+    /// request names has to be one of that account's; the admin acts on whichever account
+    /// the address belongs to
+    pub async fn account(&self, store: &Store, address: &str) -> Result<User, ApiError> {
+        let user = match self {
+            Caller::User(claims) => store
+                .get_user_by_id(&claims.sub)
+                .await
+                .map(|user| user.filter(|user| user.deleted_at.is_none())),
+            Caller::Admin => store.get_user_by_wallet_addr(address).await,
+        }
+        .map_err(|e| garden::api::internal_error(&format!("Database error: {}", e)))?
+        .ok_or_else(|| garden::api::not_found("Address not found").with_code(ApiErrorCode::UserNotFound))?;
+
+        if user.evm_addr != address && user.original_wallet_addr.as_deref() != Some(address) {
+            return Err(another_account());
+        }
+        Ok(user)
+    }
+
+    /// The user a game request plays as, always the token's own account. The admin has no
+    /// account of its own, and games aren't played on someone else's behalf
+    pub async fn player(&self, store: &Store) -> Result<User, ApiError> {
+        let Caller::User(claims) = self else {
+            return Err(ApiError::from(garden::api::bad_request("Games are played with a user token"))
+                .with_status_code(StatusCode::FORBIDDEN));
+        };
+        store
+            .get_user_by_id(&claims.sub)
+            .await
+            .map_err(|e| garden::api::internal_error(&format!("Database error: {}", e)))?
+            .filter(|user| user.deleted_at.is_none())
+            .ok_or_else(|| garden::api::not_found("User not found").with_code(ApiErrorCode::UserNotFound))
+    }
+}
+
+fn another_account() -> ApiError {
+    ApiError::from(garden::api::bad_request("Address belongs to another account"))
+        .with_status_code(StatusCode::FORBIDDEN)
+}
+
 // ============= Authentication Logic =============
 
-/// Authenticates the request using either server secret or JWT
+/// Authenticates the request using either server secret or JWT, returning the
+/// caller's address and, for JWT callers, the token's claims
 fn authenticate(
     headers: &HeaderMap,
     expected_secret: &str,
     jwt_secret: &str,
-) -> Result<String, AuthError> {
+    revoked_tokens: &RevokedTokens,
+) -> Result<(String, Option<Claims>), AuthError> {
     if let Some(_) = headers.get("X-Server-secret") {
         // If server secret header is present, validate it
         validate_server_secret(headers, expected_secret)?;
-        Ok(ADMIN_ADDRESS.to_string())
+        Ok((ADMIN_ADDRESS.to_string(), None))
     } else {
         // Otherwise, validate JWT authentication
-        validate_jwt_auth(headers, jwt_secret)
+        let claims = decode_jwt_auth(headers, jwt_secret)?;
+        ensure_not_revoked(&claims, revoked_tokens)?;
+        Ok((claims.sub.clone(), Some(claims)))
     }
 }
 
@@ -106,7 +207,7 @@ fn unauthorized_response() -> Response {
 pub fn validate_server_secret(headers: &HeaderMap, expected_secret: &str) -> Result<(), AuthError> {
     let provided_secret = get_header_value(headers, "X-Server-secret");
 
-    if provided_secret != expected_secret {
+    if !constant_time_eq(provided_secret.as_bytes(), expected_secret.as_bytes()) {
         return Err(AuthError::SignatureVerificationFailed(format!(
             "invalid server secret"
         )));
@@ -115,8 +216,24 @@ pub fn validate_server_secret(headers: &HeaderMap, expected_secret: &str) -> Res
     Ok(())
 }
 
+/// Compares two secrets in time independent of where they first differ. Both are hashed
+/// first, so their lengths don't show either
+fn constant_time_eq(provided: &[u8], expected: &[u8]) -> bool {
+    let (provided, expected) = (Sha256::digest(provided), Sha256::digest(expected));
+    provided
+        .iter()
+        .zip(expected.iter())
+        .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+        == 0
+}
+
 /// Validates the JWT Authorization header and returns the user ID if valid
 pub fn validate_jwt_auth(headers: &HeaderMap, jwt_secret: &str) -> Result<String, AuthError> {
+    decode_jwt_auth(headers, jwt_secret).map(|claims| claims.sub)
+}
+
+/// Validates the JWT Authorization header and returns its claims if valid
+pub fn decode_jwt_auth(headers: &HeaderMap, jwt_secret: &str) -> Result<Claims, AuthError> {
     let token = headers
         .get(http::header::AUTHORIZATION)
         .and_then(|h| h.to_str().ok());
@@ -130,7 +247,7 @@ pub fn validate_jwt_auth(headers: &HeaderMap, jwt_secret: &str) -> Result<String
     };
 
     // Validate the JWT token
-    validate_jwt(valid_token, jwt_secret)
+    decode_jwt(valid_token, jwt_secret)
 }
 
 // ============= Helper Functions =============
@@ -144,20 +261,39 @@ fn get_header_value(headers: &HeaderMap, key: &str) -> String {
         .to_string()
 }
 
-/// Decodes and validates a JWT, returning the user ID if valid and not expired
-pub fn validate_jwt(jwt: &str, secret: &str) -> Result<String, AuthError> {
-    // Decode and validate the JWT token
-    let token = decode::<Claims>(
-        jwt,
-        &DecodingKey::from_secret(secret.as_ref()),
-        &Validation::default(),
-    )
-    .map_err(|e| AuthError::SignatureVerificationFailed(e.to_string()))?;
+/// Rejects tokens whose id was revoked by logout
+pub fn ensure_not_revoked(claims: &Claims, revoked_tokens: &RevokedTokens) -> Result<(), AuthError> {
+    if !claims.jti.is_empty() && revoked_tokens.contains_key(&claims.jti) {
+        return Err(AuthError::SignatureVerificationFailed(
+            "Token has been revoked".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Decodes and validates a JWT, returning its claims if valid and not expired
+pub fn decode_jwt(jwt: &str, secret: &str) -> Result<Claims, AuthError> {
+    // Expiry is enforced here, with no leeway so a token is dead the moment `exp` passes
+    let mut validation = Validation::default();
+    validation.leeway = 0;
 
-    // Check if token has expired
-    is_expired(token.claims.exp as u64)?;
+    let token = decode::<Claims>(jwt, &DecodingKey::from_secret(secret.as_ref()), &validation)
+        .map_err(|e| AuthError::SignatureVerificationFailed(e.to_string()))?;
 
-    Ok(token.claims.sub)
+    Ok(token.claims)
+}
+
+/// Mints a JWT for the given user ID, returning the token and its expiry timestamp
+pub fn create_jwt(sub: &str, ttl_secs: u64, secret: &str) -> Result<(String, usize), AuthError> {
+    let exp = (get_current_timestamp()? + ttl_secs) as usize;
+    let token = encode(
+        &Header::default(),
+        &Claims::new(sub.to_string(), exp),
+        &EncodingKey::from_secret(secret.as_ref()),
+    )
+    .map_err(|e| AuthError::InternalError(e.to_string()))?;
+
+    Ok((token, exp))
 }
 
 /// Get the current Unix timestamp in seconds
@@ -168,19 +304,6 @@ fn get_current_timestamp() -> Result<u64, AuthError> {
         .map_err(|e| AuthError::InternalError(e.to_string()))
 }
 
-/// Checks if the given expiration timestamp is in the past
-fn is_expired(expiration: u64) -> Result<(), AuthError> {
-    let current_timestamp = get_current_timestamp()?;
-
-    if current_timestamp >= expiration {
-        Err(AuthError::SignatureVerificationFailed(format!(
-            "Token expired at {expiration}, current time is {current_timestamp}"
-        )))
-    } else {
-        Ok(())
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -200,10 +323,7 @@ mod tests {
             .unwrap()
             .as_secs();
 
-        let claims = Claims {
-            sub: user_id.to_string(),
-            exp: (now as i64 + exp_offset_secs) as usize,
-        };
+        let claims = Claims::new(user_id.to_string(), (now as i64 + exp_offset_secs) as usize);
 
         encode(
             &Header::default(),
@@ -238,6 +358,7 @@ mod tests {
             .layer(AuthLayer {
                 expected_secret: TEST_SECRET.to_string(),
                 jwt_secret: TEST_JWT_SECRET.to_string(),
+                revoked_tokens: crate::auth::new_revocation_cache(),
             })
             .service_fn(|req: Request<Body>| async move {
                 let body = if let Some(user_id) = req.extensions().get::<String>() {
@@ -286,6 +407,15 @@ mod tests {
             assert!(result.is_err());
         }
 
+        #[tokio::test]
+        async fn test_short_lived_jwt_rejected_after_ttl() {
+            let (token, _) = create_jwt(TEST_USER_ID, 1, TEST_JWT_SECRET).unwrap();
+            assert_eq!(decode_jwt(&token, TEST_JWT_SECRET).unwrap().sub, TEST_USER_ID);
+
+            tokio::time::sleep(std::time::Duration::from_millis(2100)).await;
+            assert!(decode_jwt(&token, TEST_JWT_SECRET).is_err());
+        }
+
         #[tokio::test]
         async fn test_invalid_jwt_token() {
             let mut headers = HeaderMap::new();
@@ -333,6 +463,16 @@ mod tests {
             assert!(result.is_err());
         }
 
+        #[tokio::test]
+        async fn test_server_secret_must_match_exactly() {
+            // The header's own name is the obvious guess at a default secret
+            let headers = create_server_secret_headers("X-Server-secret");
+            assert!(validate_server_secret(&headers, TEST_SECRET).is_err());
+            let prefix = create_server_secret_headers(&TEST_SECRET[..TEST_SECRET.len() - 1]);
+            assert!(validate_server_secret(&prefix, TEST_SECRET).is_err());
+            assert!(validate_server_secret(&HeaderMap::new(), TEST_SECRET).is_err());
+        }
+
         #[tokio::test]
         async fn test_jwt_with_wrong_secret() {
             let token = create_test_jwt(TEST_USER_ID, 3600);
@@ -349,18 +489,20 @@ mod tests {
         #[tokio::test]
         async fn test_authenticate_with_server_secret() {
             let headers = create_server_secret_headers(TEST_SECRET);
-            let result = authenticate(&headers, TEST_SECRET, TEST_JWT_SECRET);
+            let revoked = crate::auth::new_revocation_cache();
+            let result = authenticate(&headers, TEST_SECRET, TEST_JWT_SECRET, &revoked);
             assert!(result.is_ok());
-            assert_eq!(result.unwrap(), ADMIN_ADDRESS);
+            assert_eq!(result.unwrap().0, ADMIN_ADDRESS);
         }
 
         #[tokio::test]
         async fn test_authenticate_with_jwt() {
             let token = create_test_jwt(TEST_USER_ID, 3600);
             let headers = create_jwt_headers(&token);
-            let result = authenticate(&headers, TEST_SECRET, TEST_JWT_SECRET);
+            let revoked = crate::auth::new_revocation_cache();
+            let result = authenticate(&headers, TEST_SECRET, TEST_JWT_SECRET, &revoked);
             assert!(result.is_ok());
-            assert_eq!(result.unwrap(), TEST_USER_ID);
+            assert_eq!(result.unwrap().0, TEST_USER_ID);
         }
     }
 
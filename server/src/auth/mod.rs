@@ -1,8 +1,21 @@
 mod middleware;
+mod rate_limit;
 mod router;
+mod siwe;
 pub use middleware::*;
+pub use rate_limit::*;
 pub use router::*;
+pub use siwe::*;
+use argon2::{
+    Argon2,
+    password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString, rand_core::OsRng},
+};
+use moka::{Expiry, future::Cache};
+use rand::Rng;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -19,15 +32,71 @@ pub enum AuthError {
 pub struct Claims {
     pub sub: String, // user_id
     pub exp: usize,  // expiration
+    #[serde(default)]
+    pub jti: String, // Unique token id, used to revoke a single token on logout
 }
 impl Claims {
     pub fn new(sub: String, exp: usize) -> Self {
-        Self { sub, exp }
+        Self {
+            sub,
+            exp,
+            jti: uuid::Uuid::new_v4().to_string(),
+        }
     }
 }
 
+/// Revoked token ids mapped to their `exp`; each entry lives only as long as the
+/// token would have, after which expiry rejects it anyway
+pub type RevokedTokens = Cache<String, usize>;
+
+struct UntilTokenExpiry;
+
+impl Expiry<String, usize> for UntilTokenExpiry {
+    fn expire_after_create(&self, _jti: &String, exp: &usize, _created_at: Instant) -> Option<Duration> {
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        Some(Duration::from_secs((*exp as u64).saturating_sub(now)))
+    }
+}
+
+pub fn new_revocation_cache() -> Arc<RevokedTokens> {
+    Arc::new(Cache::builder().expire_after(UntilTokenExpiry).build())
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuthRequest {
     username: String,
     pass: String,
+    #[serde(default)]
+    btc_addr: Option<String>, // Registration only; a BTC address is generated when absent
+}
+
+/// Hashes a plaintext password with Argon2 and a random salt
+pub fn hash_password(pass: &str) -> Result<String, AuthError> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(pass.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| AuthError::InternalError(e.to_string()))
+}
+
+/// Generates an opaque refresh token; only its hash is ever stored
+pub fn generate_refresh_token() -> String {
+    let bytes: [u8; 32] = rand::thread_rng().r#gen();
+    hex::encode(bytes)
+}
+
+/// Hashes a refresh token for storage and lookup. The token is already high-entropy,
+/// so a fast hash is enough
+pub fn hash_refresh_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// Checks a plaintext password against a stored Argon2 hash
+pub fn verify_password(pass: &str, hash: &str) -> bool {
+    PasswordHash::new(hash)
+        .map(|parsed| Argon2::default().verify_password(pass.as_bytes(), &parsed).is_ok())
+        .unwrap_or(false)
 }
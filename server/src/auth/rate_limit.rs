@@ -0,0 +1,191 @@
+use alloy::transports::BoxFuture;
+use axum::extract::{ConnectInfo, Request};
+use axum::http::StatusCode;
+use axum::response::Response;
+use moka::future::Cache;
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tower::{Layer, Service};
+
+use crate::auth::validate_jwt_auth;
+use crate::primitives::with_status;
+
+/// Sliding window over which request limits are counted
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+
+/// Timestamps of a client's requests within the current window
+type RequestLog = Arc<Mutex<VecDeque<Instant>>>;
+
+/// Layer that throttles requests per client, with a separate per-minute limit for each
+/// route group. Requests outside every group pass through untouched
+#[derive(Clone)]
+pub struct RateLimitLayer {
+    groups: Arc<Vec<(String, usize)>>, // (path prefix, requests per minute)
+    jwt_secret: String,                // Used to key authenticated callers by user id
+    requests: Arc<Cache<(usize, String), RequestLog>>, // Keyed by (group index, client)
+}
+
+impl RateLimitLayer {
+    pub fn new(jwt_secret: String) -> Self {
+        Self {
+            groups: Arc::new(Vec::new()),
+            jwt_secret,
+            // Idle clients are forgotten once their whole window has passed
+            requests: Arc::new(
+                Cache::builder()
+                    .time_to_idle(RATE_LIMIT_WINDOW)
+                    .build(),
+            ),
+        }
+    }
+
+    /// Limit every route under `prefix` to `per_minute` requests per client
+    pub fn group(mut self, prefix: &str, per_minute: usize) -> Self {
+        Arc::make_mut(&mut self.groups).push((prefix.to_string(), per_minute));
+        self
+    }
+
+    /// Finds the first group whose prefix covers the path
+    fn group_for(&self, path: &str) -> Option<(usize, usize)> {
+        self.groups
+            .iter()
+            .enumerate()
+            .find(|(_, (prefix, _))| {
+                path.strip_prefix(prefix.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+            })
+            .map(|(index, (_, limit))| (index, *limit))
+    }
+
+    /// Authenticated callers are keyed by user id, everyone else by peer IP
+    fn client_key(&self, req: &Request) -> String {
+        if let Ok(user_id) = validate_jwt_auth(req.headers(), &self.jwt_secret) {
+            return format!("user:{}", user_id);
+        }
+        match req.extensions().get::<ConnectInfo<SocketAddr>>() {
+            Some(ConnectInfo(addr)) => format!("ip:{}", addr.ip()),
+            None => "ip:unknown".to_string(),
+        }
+    }
+
+    /// Records the request and reports whether it fits within the limit
+    async fn allow(&self, group: usize, client: String, limit: usize) -> bool {
+        let log = self
+            .requests
+            .get_with((group, client), async { RequestLog::default() })
+            .await;
+        let mut log = log.lock().unwrap();
+
+        let now = Instant::now();
+        while log
+            .front()
+            .is_some_and(|sent| now.duration_since(*sent) >= RATE_LIMIT_WINDOW)
+        {
+            log.pop_front();
+        }
+        if log.len() >= limit {
+            return false;
+        }
+        log.push_back(now);
+        true
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimitMiddleware<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimitMiddleware {
+            inner,
+            limiter: self.clone(),
+        }
+    }
+}
+
+/// Middleware that rejects requests over their group's limit with 429
+#[derive(Clone)]
+pub struct RateLimitMiddleware<S> {
+    inner: S,
+    limiter: RateLimitLayer,
+}
+
+impl<S> Service<Request> for RateLimitMiddleware<S>
+where
+    S: Service<Request, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        let limiter = self.limiter.clone();
+        let mut inner = self.inner.clone();
+
+        Box::pin(async move {
+            if let Some((group, limit)) = limiter.group_for(req.uri().path()) {
+                let client = limiter.client_key(&req);
+                if !limiter.allow(group, client, limit).await {
+                    return Ok(with_status(
+                        StatusCode::TOO_MANY_REQUESTS,
+                        garden::api::bad_request("Too many requests, slow down"),
+                    ));
+                }
+            }
+            inner.call(req).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::create_jwt;
+    use axum::{Router, body::Body, routing::post};
+    use tower::ServiceExt;
+
+    const TEST_JWT_SECRET: &str = "rate_limit_secret";
+
+    fn app(limit: usize) -> Router {
+        Router::new()
+            .route("/auth/login", post(|| async { "ok" }))
+            .route("/wallet/health", post(|| async { "ok" }))
+            .layer(RateLimitLayer::new(TEST_JWT_SECRET.to_string()).group("/auth", limit))
+    }
+
+    async fn post_as(app: &Router, uri: &str, token: Option<&str>) -> StatusCode {
+        let mut request = Request::builder().method("POST").uri(uri);
+        if let Some(token) = token {
+            request = request.header("Authorization", format!("Bearer {}", token));
+        }
+        let request = request.body(Body::empty()).unwrap();
+        app.clone().oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn test_request_over_limit_gets_429() {
+        let app = app(3);
+        for _ in 0..3 {
+            assert_eq!(post_as(&app, "/auth/login", None).await, StatusCode::OK);
+        }
+        assert_eq!(
+            post_as(&app, "/auth/login", None).await,
+            StatusCode::TOO_MANY_REQUESTS
+        );
+
+        // Routes outside a group and other clients are unaffected
+        assert_eq!(post_as(&app, "/wallet/health", None).await, StatusCode::OK);
+        let (token, _) = create_jwt("user-1", 3600, TEST_JWT_SECRET).unwrap();
+        assert_eq!(
+            post_as(&app, "/auth/login", Some(&token)).await,
+            StatusCode::OK
+        );
+    }
+}
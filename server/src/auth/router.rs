@@ -1,13 +1,64 @@
-use crate::server::AppState;
+use crate::{
+    auth::{
+        AuthError, AuthRequest, Claims, SiweMessage, create_jwt, generate_refresh_token, hash_password, hash_refresh_token,
+        verify_password,
+    },
+    primitives::{HttpResult, with_status},
+    server::AppState,
+    store::{GameTransaction, StoreError, User},
+    wallet::{WalletGenerator, find_or_create_wallet_user, validate_btc_address},
+};
 use axum::{
-    Extension, Router,
+    Extension, Json, Router,
     extract::State,
-    routing::get,
+    http::StatusCode,
+    response::IntoResponse,
+    routing::{get, post},
 };
 use garden::api::primitives::{ApiResult, Response};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use sqlx::types::BigDecimal;
 use std::sync::Arc;
 
+#[derive(Serialize)]
+struct RegisterResponse {
+    user_id: String,
+    evm_addr: String,
+    btc_addr: Option<String>,
+}
+
+#[derive(Serialize)]
+struct LoginResponse {
+    token: String,
+    expires_at: usize,
+    refresh_token: String, // Single use; exchanged at /auth/refresh for a new pair
+}
+
+#[derive(Deserialize)]
+struct RefreshRequest {
+    refresh_token: String,
+}
+
+#[derive(Deserialize)]
+struct ChangePasswordRequest {
+    old_pass: String,
+    new_pass: String,
+}
+
+#[derive(Serialize)]
+struct SiweNonceResponse {
+    nonce: String,
+}
+
+#[derive(Deserialize)]
+struct SiweVerifyRequest {
+    message: String,   // The full EIP-4361 message that was signed
+    signature: String, // 65-byte personal_sign signature, hex encoded
+}
+
+// Lifetime of refresh tokens, which outlive the short access tokens they renew
+const REFRESH_TOKEN_TTL_SECS: i64 = 30 * 24 * 60 * 60;
+
 #[derive(Serialize)]
 struct UserBalanceResponse {
     ethereum: EthereumBalance,
@@ -23,47 +74,740 @@ struct EthereumBalance {
 
 #[derive(Serialize)]
 struct BitcoinBalance {
-    address: String,
+    address: Option<String>, // None for wallet-connected users, who never registered one
     account_balance: f64,
     in_game_balance: f64,
 }
 
 async fn get_user_balance(
     State(state): State<Arc<AppState>>,
-    Extension(user_addr): Extension<String>,
+    Extension(user_id): Extension<String>,
 ) -> ApiResult<UserBalanceResponse> {
-    // Get user from database
-    let user = state.store.get_user_by_wallet_addr(&user_addr).await
+    // The auth middleware hands over the token's subject, a user id
+    let user = state.store.get_user_by_id(&user_id).await
         .map_err(|e| garden::api::internal_error(&format!("Database error: {}", e)))?
+        .filter(|user| user.deleted_at.is_none())
         .ok_or_else(|| garden::api::not_found("User not found"))?;
 
     // Convert BigDecimal to f64 for the response
-    let account_balance = user.account_balance.to_string().parse::<f64>()
-        .unwrap_or(0.0);
-    let in_game_balance = user.in_game_balance.to_string().parse::<f64>()
-        .unwrap_or(0.0);
+    let to_f64 = |balance: &BigDecimal| balance.to_string().parse::<f64>().unwrap_or(0.0);
 
-    // For now, we'll return the same balance for both ETH and BTC
-    // In a real system, you'd have separate balances per chain
     let response = UserBalanceResponse {
         ethereum: EthereumBalance {
             address: user.evm_addr.clone(),
-            account_balance,
-            in_game_balance,
+            account_balance: to_f64(&user.account_balance),
+            in_game_balance: to_f64(&user.in_game_balance),
         },
         bitcoin: BitcoinBalance {
-            address: user.evm_addr.clone(), // Using EVM address for now
-            account_balance,
-            in_game_balance,
+            address: user.btc_addr.clone(),
+            account_balance: to_f64(&user.btc_account_balance),
+            in_game_balance: to_f64(&user.btc_in_game_balance),
         },
     };
 
     Ok(Response::ok(response))
 }
 
+// Register a username/password user with a server-generated game wallet
+async fn register(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<AuthRequest>,
+) -> HttpResult<RegisterResponse> {
+    let username = payload.username.trim();
+    if username.is_empty() || payload.pass.is_empty() {
+        return Err(garden::api::bad_request("Username and password are required").into_response());
+    }
+
+    // Take the caller's BTC address as is, or generate one the server holds the key for
+    let (btc_pk, btc_addr) = match payload.btc_addr {
+        Some(address) => {
+            validate_btc_address(&address).map_err(IntoResponse::into_response)?;
+            (None, address)
+        }
+        None => {
+            let (pk, address) = WalletGenerator::generate_btc_wallet()
+                .map_err(|e| garden::api::internal_error(&format!("Failed to generate BTC wallet: {}", e)).into_response())?;
+            (Some(pk), address)
+        }
+    };
+
+    let password_hash = hash_password(&payload.pass)
+        .map_err(|e| garden::api::internal_error(&format!("Failed to hash password: {}", e)).into_response())?;
+
+    let (evm_private_key, evm_address) = WalletGenerator::generate_evm_wallet().await
+        .map_err(|e| garden::api::internal_error(&format!("Failed to generate EVM wallet: {}", e)).into_response())?;
+
+    let new_user = User {
+        btc_addr: Some(btc_addr),
+        btc_pk,
+        ..User::new(
+            String::new(), // user_id will be generated by database
+            username.to_string(),
+            password_hash,
+            evm_private_key,
+            evm_address,
+            None, // no connected wallet for username/password users
+            BigDecimal::from(0),
+            BigDecimal::from(0),
+        )
+    };
+
+    let created_user = state.store.create_user(&new_user).await.map_err(|e| match e {
+        // The unique index on username rejects duplicates
+        StoreError::UniqueViolation(_) => {
+            with_status(StatusCode::CONFLICT, garden::api::bad_request("Username already exists"))
+        }
+        e => garden::api::internal_error(&format!("Failed to create user: {}", e)).into_response(),
+    })?;
+
+    // Play money only: the bonus never reaches account_balance, so it can't be cashed out as such
+    if state.signup_bonus > BigDecimal::from(0) {
+        state.store.adjust_in_game_balance(&created_user.user_id, &state.signup_bonus).await
+            .map_err(|e| garden::api::internal_error(&format!("Failed to credit signup bonus: {}", e)).into_response())?;
+        let bonus_transaction = GameTransaction {
+            id: String::new(),
+            user_id: created_user.user_id.clone(),
+            transaction_type: "deposit".to_string(),
+            amount: state.signup_bonus.clone(),
+            game_type: None,
+            game_session_id: None,
+            description: Some("signup bonus".to_string()),
+            created_at: None,
+        };
+        state.store.create_transaction(&bonus_transaction).await
+            .map_err(|e| garden::api::internal_error(&format!("Failed to record signup bonus: {}", e)).into_response())?;
+        // Has to be bet once over before any of the balance can be cashed out
+        state.store.add_bonus_wagering(&created_user.user_id, &state.signup_bonus).await
+            .map_err(|e| garden::api::internal_error(&format!("Failed to set bonus wagering: {}", e)).into_response())?;
+    }
+
+    Ok(Response::ok(RegisterResponse {
+        user_id: created_user.user_id,
+        evm_addr: created_user.evm_addr,
+        btc_addr: created_user.btc_addr,
+    }))
+}
+
+// Exchange username/password for a JWT whose subject is the user_id
+async fn login(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<AuthRequest>,
+) -> HttpResult<LoginResponse> {
+    let user = state.store.get_user_by_username(payload.username.trim()).await
+        .map_err(|e| garden::api::internal_error(&format!("Database error: {}", e)).into_response())?;
+
+    // Same response for unknown users and wrong passwords so usernames can't be probed
+    let user = match user {
+        Some(user) if verify_password(&payload.pass, &user.password) => user,
+        _ => {
+            return Err(with_status(
+                StatusCode::UNAUTHORIZED,
+                garden::api::bad_request("Invalid username or password"),
+            ));
+        }
+    };
+
+    start_session(&state, &user.user_id).await
+}
+
+// Hand out a single-use nonce for a Sign-In with Ethereum message
+async fn siwe_nonce(State(state): State<Arc<AppState>>) -> ApiResult<SiweNonceResponse> {
+    let nonce = uuid::Uuid::new_v4().simple().to_string();
+    state.siwe_nonces.insert(nonce.clone(), ()).await;
+    Ok(Response::ok(SiweNonceResponse { nonce }))
+}
+
+// Exchange a signed EIP-4361 message for tokens, proving ownership of the wallet
+async fn siwe_verify(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<SiweVerifyRequest>,
+) -> HttpResult<LoginResponse> {
+    let unauthorized = |e: AuthError| with_status(StatusCode::UNAUTHORIZED, garden::api::bad_request(&e.to_string()));
+
+    let message = SiweMessage::parse(&payload.message).map_err(unauthorized)?;
+    message.verify(&payload.message, &payload.signature).map_err(unauthorized)?;
+
+    // A message signed for another site, even one that fetched our nonce, doesn't sign in here
+    if message.domain != state.siwe_domain {
+        return Err(with_status(
+            StatusCode::UNAUTHORIZED,
+            garden::api::bad_request(&format!("SIWE message is for {}, not {}", message.domain, state.siwe_domain)),
+        ));
+    }
+    if message.chain_id != state.siwe_chain_id {
+        return Err(with_status(
+            StatusCode::UNAUTHORIZED,
+            garden::api::bad_request(&format!("SIWE message is for chain {}, not {}", message.chain_id, state.siwe_chain_id)),
+        ));
+    }
+
+    // Nonces are only issued by this server and consumed here, so a signed message
+    // can't be replayed
+    if state.siwe_nonces.remove(&message.nonce).await.is_none() {
+        return Err(with_status(
+            StatusCode::UNAUTHORIZED,
+            garden::api::bad_request("Unknown or already used nonce"),
+        ));
+    }
+
+    // Wallets may have been connected with either address casing
+    let existing_user = state.store.get_user_by_wallet_addr(&format!("{:#x}", message.address)).await
+        .map_err(|e| garden::api::internal_error(&format!("Database error: {}", e)).into_response())?;
+    let user = match existing_user {
+        Some(user) => user,
+        None => find_or_create_wallet_user(&message.address.to_checksum(None), &state.store)
+            .await
+            .map_err(IntoResponse::into_response)?
+            .0,
+    };
+
+    tracing::info!("SIWE sign-in for {}", message.address);
+    start_session(&state, &user.user_id).await
+}
+
+// Issue a fresh access/refresh token pair for a user who just authenticated
+async fn start_session(state: &AppState, user_id: &str) -> HttpResult<LoginResponse> {
+    let refresh_token = generate_refresh_token();
+    state
+        .store
+        .create_refresh_token(&hash_refresh_token(&refresh_token), user_id, REFRESH_TOKEN_TTL_SECS)
+        .await
+        .map_err(|e| garden::api::internal_error(&format!("Failed to issue token: {}", e)).into_response())?;
+
+    let tokens = issue_tokens(state, user_id, refresh_token)
+        .map_err(|e| garden::api::internal_error(&format!("Failed to issue token: {}", e)).into_response())?;
+    Ok(Response::ok(tokens))
+}
+
+// Trade a refresh token for a new access token, rotating the refresh token so the
+// presented one can't be replayed
+async fn refresh(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<RefreshRequest>,
+) -> HttpResult<LoginResponse> {
+    let refresh_token = generate_refresh_token();
+    let user_id = state
+        .store
+        .rotate_refresh_token(
+            &hash_refresh_token(&payload.refresh_token),
+            &hash_refresh_token(&refresh_token),
+            REFRESH_TOKEN_TTL_SECS,
+        )
+        .await
+        .map_err(|e| garden::api::internal_error(&format!("Database error: {}", e)).into_response())?
+        .ok_or_else(|| {
+            with_status(
+                StatusCode::UNAUTHORIZED,
+                garden::api::bad_request("Invalid or expired refresh token"),
+            )
+        })?;
+
+    let tokens = issue_tokens(&state, &user_id, refresh_token)
+        .map_err(|e| garden::api::internal_error(&format!("Failed to issue token: {}", e)).into_response())?;
+    Ok(Response::ok(tokens))
+}
+
+// Mint an access token and pair it with an already stored refresh token
+fn issue_tokens(state: &AppState, user_id: &str, refresh_token: String) -> Result<LoginResponse, AuthError> {
+    let (token, expires_at) = create_jwt(user_id, state.jwt_ttl_secs, &state.jwt_secret)?;
+    Ok(LoginResponse { token, expires_at, refresh_token })
+}
+
+// Revoke the presented access token until it would have expired anyway
+async fn logout(
+    State(state): State<Arc<AppState>>,
+    claims: Option<Extension<Claims>>,
+) -> ApiResult<()> {
+    let Some(Extension(claims)) = claims else {
+        return Err(garden::api::bad_request("Logout requires a user token"));
+    };
+    if claims.jti.is_empty() {
+        return Err(garden::api::bad_request("Token cannot be revoked"));
+    }
+    state.revoked_tokens.insert(claims.jti, claims.exp).await;
+    Ok(Response::ok(()))
+}
+
+// Replace the caller's password. Every refresh token and the presented access token are
+// revoked, so other sessions have to log in again, and the caller gets a fresh pair
+async fn change_password(
+    State(state): State<Arc<AppState>>,
+    claims: Option<Extension<Claims>>,
+    Json(payload): Json<ChangePasswordRequest>,
+) -> HttpResult<LoginResponse> {
+    let Some(Extension(claims)) = claims else {
+        return Err(garden::api::bad_request("Changing password requires a user token").into_response());
+    };
+    if payload.new_pass.is_empty() {
+        return Err(garden::api::bad_request("New password is required").into_response());
+    }
+
+    let user = state.store.get_user_by_id(&claims.sub).await
+        .map_err(|e| garden::api::internal_error(&format!("Database error: {}", e)).into_response())?
+        .ok_or_else(|| garden::api::not_found("User not found").into_response())?;
+    if !verify_password(&payload.old_pass, &user.password) {
+        return Err(with_status(
+            StatusCode::UNAUTHORIZED,
+            garden::api::bad_request("Invalid password"),
+        ));
+    }
+
+    let password_hash = hash_password(&payload.new_pass)
+        .map_err(|e| garden::api::internal_error(&format!("Failed to hash password: {}", e)).into_response())?;
+    state.store.update_password(&user.user_id, &password_hash).await
+        .map_err(|e| garden::api::internal_error(&format!("Failed to update password: {}", e)).into_response())?;
+    state.store.revoke_refresh_tokens(&user.user_id).await
+        .map_err(|e| garden::api::internal_error(&format!("Failed to revoke tokens: {}", e)).into_response())?;
+    if !claims.jti.is_empty() {
+        state.revoked_tokens.insert(claims.jti, claims.exp).await;
+    }
+
+    start_session(&state, &user.user_id).await
+}
 
 pub async fn router(state: Arc<AppState>) -> Router {
     Router::new()
         .route("/user", get(get_user_balance))
+        .route("/auth/logout", post(logout))
+        .route("/auth/change-password", post(change_password))
         .with_state(state)
 }
+
+// Routes reachable without a token (registration and login)
+pub async fn public_router(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/auth/register", post(register))
+        .route("/auth/login", post(login))
+        .route("/auth/refresh", post(refresh))
+        .route("/auth/siwe/nonce", get(siwe_nonce))
+        .route("/auth/siwe/verify", post(siwe_verify))
+        .with_state(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, extract::Request, http::Method};
+    use tower::ServiceExt;
+
+    async fn post_credentials(
+        app: Router,
+        uri: &str,
+        username: &str,
+        pass: &str,
+    ) -> axum::response::Response {
+        let payload = serde_json::json!({ "username": username, "pass": pass });
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(uri)
+            .header("Content-Type", "application/json")
+            .body(Body::from(serde_json::to_vec(&payload).unwrap()))
+            .unwrap();
+        app.oneshot(request).await.unwrap()
+    }
+
+    async fn post_register(app: Router, username: &str, pass: &str) -> StatusCode {
+        post_credentials(app, "/auth/register", username, pass).await.status()
+    }
+
+    #[tokio::test]
+    async fn test_register_creates_user_with_wallet() {
+        let state = Arc::new(AppState::default().await);
+        let app = public_router(state.clone()).await;
+        let username = format!("user_{}", uuid::Uuid::new_v4());
+
+        let status = post_register(app, &username, "hunter2").await;
+        assert_eq!(status, StatusCode::OK);
+
+        let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE username = $1")
+            .bind(&username)
+            .fetch_one(state.store.pool())
+            .await
+            .unwrap();
+        let found = state.store.get_user_by_evm_addr(&user.evm_addr).await.unwrap();
+        assert!(found.is_some());
+        assert_ne!(found.unwrap().password, "hunter2");
+    }
+
+    #[tokio::test]
+    async fn test_register_credits_signup_bonus_to_play_with_only() {
+        let mut state = AppState::default().await;
+        state.signup_bonus = BigDecimal::from(5);
+        let state = Arc::new(state);
+        let username = format!("user_{}", uuid::Uuid::new_v4());
+
+        let status = post_register(public_router(state.clone()).await, &username, "hunter2").await;
+        assert_eq!(status, StatusCode::OK);
+
+        let user = state.store.get_user_by_username(&username).await.unwrap().unwrap();
+        assert_eq!(user.in_game_balance, BigDecimal::from(5));
+        assert_eq!(user.account_balance, BigDecimal::from(0));
+        assert_eq!(user.bonus_wagering_remaining, BigDecimal::from(5));
+        let transactions = state.store.get_user_transactions(&user.user_id, None).await.unwrap();
+        assert_eq!(transactions.len(), 1);
+        assert_eq!(transactions[0].transaction_type, "deposit");
+        assert_eq!(transactions[0].description.as_deref(), Some("signup bonus"));
+
+        // Losing part of it leaves nothing that can leave the platform
+        state.store.adjust_in_game_balance(&user.user_id, &BigDecimal::from(-2)).await.unwrap();
+        let claims = crate::auth::Claims::new(user.user_id.clone(), usize::MAX);
+        let wallet = crate::wallet::authenticated_router(state.clone()).await.layer(Extension(claims));
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(format!("/cashout/{}", user.evm_addr))
+            .header("Content-Type", "application/json")
+            .body(Body::from(serde_json::to_vec(&serde_json::json!({ "amount": "3" })).unwrap()))
+            .unwrap();
+        let response = wallet.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["code"], "BONUS_WAGERING_INCOMPLETE");
+        let user = state.store.get_user_by_id(&user.user_id).await.unwrap().unwrap();
+        assert_eq!(user.in_game_balance, BigDecimal::from(3));
+        assert_eq!(user.account_balance, BigDecimal::from(0));
+    }
+
+    #[tokio::test]
+    async fn test_register_rejects_duplicate_and_empty() {
+        let state = Arc::new(AppState::default().await);
+        let app = public_router(state).await;
+        let username = format!("user_{}", uuid::Uuid::new_v4());
+
+        assert_eq!(post_register(app.clone(), &username, "pass").await, StatusCode::OK);
+        assert_eq!(post_register(app.clone(), &username, "pass").await, StatusCode::CONFLICT);
+        assert_eq!(post_register(app, "", "pass").await, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_register_generates_or_accepts_btc_address() {
+        let state = Arc::new(AppState::default().await);
+        let app = public_router(state.clone()).await;
+        let register = |username: String, btc_addr: Option<&str>| {
+            let payload = serde_json::json!({ "username": username, "pass": "pass", "btc_addr": btc_addr });
+            let request = Request::builder()
+                .method(Method::POST)
+                .uri("/auth/register")
+                .header("Content-Type", "application/json")
+                .body(Body::from(serde_json::to_vec(&payload).unwrap()))
+                .unwrap();
+            app.clone().oneshot(request)
+        };
+
+        let username = format!("user_{}", uuid::Uuid::new_v4());
+        assert_eq!(register(username.clone(), None).await.unwrap().status(), StatusCode::OK);
+        let generated = state.store.get_user_by_username(&username).await.unwrap().unwrap();
+        assert!(generated.btc_addr.unwrap().starts_with("tb1"));
+        assert!(generated.btc_pk.is_some());
+
+        let (_, btc_addr) = WalletGenerator::generate_btc_wallet().unwrap();
+        let username = format!("user_{}", uuid::Uuid::new_v4());
+        assert_eq!(register(username.clone(), Some(&btc_addr)).await.unwrap().status(), StatusCode::OK);
+        let accepted = state.store.get_user_by_username(&username).await.unwrap().unwrap();
+        assert_eq!(accepted.btc_addr, Some(btc_addr));
+        assert!(accepted.btc_pk.is_none());
+
+        let username = format!("user_{}", uuid::Uuid::new_v4());
+        let status = register(username, Some("0x0000000000000000000000000000000000000000")).await.unwrap().status();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_eth_and_btc_balances_move_independently() {
+        let state = Arc::new(AppState::default().await);
+        let username = format!("user_{}", uuid::Uuid::new_v4());
+        let register = public_router(state.clone()).await;
+        assert_eq!(post_register(register, &username, "pass").await, StatusCode::OK);
+        let user = state.store.get_user_by_username(&username).await.unwrap().unwrap();
+
+        state.store.adjust_account_balance(&user.user_id, &BigDecimal::from(3)).await.unwrap();
+        state.store.adjust_in_game_balance(&user.user_id, &BigDecimal::from(2)).await.unwrap();
+        state.store.adjust_btc_account_balance(&user.user_id, &BigDecimal::from(5)).await.unwrap();
+        state.store.adjust_btc_in_game_balance(&user.user_id, &BigDecimal::from(4)).await.unwrap();
+        state.store.adjust_btc_in_game_balance(&user.user_id, &BigDecimal::from(-1)).await.unwrap();
+
+        let app = router(state.clone()).await.layer(Extension(user.user_id.clone()));
+        let request = Request::builder().uri("/user").body(Body::empty()).unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body["result"]["ethereum"]["account_balance"], 3.0);
+        assert_eq!(body["result"]["ethereum"]["in_game_balance"], 2.0);
+        assert_eq!(body["result"]["bitcoin"]["address"], user.btc_addr.unwrap().as_str());
+        assert_eq!(body["result"]["bitcoin"]["account_balance"], 5.0);
+        assert_eq!(body["result"]["bitcoin"]["in_game_balance"], 3.0);
+    }
+
+    #[tokio::test]
+    async fn test_login_issues_jwt_for_user_id() {
+        let state = Arc::new(AppState::default().await);
+        let app = public_router(state.clone()).await;
+        let username = format!("user_{}", uuid::Uuid::new_v4());
+        assert_eq!(post_register(app.clone(), &username, "pass").await, StatusCode::OK);
+
+        let response = post_credentials(app.clone(), "/auth/login", &username, "pass").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let token = body["result"]["token"].as_str().unwrap();
+
+        let user = state.store.get_user_by_username(&username).await.unwrap().unwrap();
+        let sub = crate::auth::decode_jwt(token, &state.jwt_secret).unwrap().sub;
+        assert_eq!(sub, user.user_id);
+
+        let wrong_pass = post_credentials(app.clone(), "/auth/login", &username, "nope").await;
+        assert_eq!(wrong_pass.status(), StatusCode::UNAUTHORIZED);
+        let unknown_user = post_credentials(app, "/auth/login", "no_such_user_x", "pass").await;
+        assert_eq!(unknown_user.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_user_balance_with_jwt() {
+        let state = Arc::new(AppState::default().await);
+        let app = Router::new()
+            .merge(router(state.clone()).await.layer(crate::auth::AuthLayer {
+                expected_secret: crate::mines::generate_seed(),
+                jwt_secret: state.jwt_secret.clone(),
+                revoked_tokens: state.revoked_tokens.clone(),
+            }))
+            .merge(public_router(state.clone()).await);
+        let username = format!("user_{}", uuid::Uuid::new_v4());
+        assert_eq!(post_register(app.clone(), &username, "pass").await, StatusCode::OK);
+        let user = state.store.get_user_by_username(&username).await.unwrap().unwrap();
+        state.store.adjust_in_game_balance(&user.user_id, &BigDecimal::from(2)).await.unwrap();
+
+        let response = post_credentials(app.clone(), "/auth/login", &username, "pass").await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let token = body["result"]["token"].as_str().unwrap().to_string();
+
+        // The token's subject is the user id, which has to resolve to the account
+        let request = Request::builder()
+            .uri("/user")
+            .header("Authorization", format!("Bearer {}", token))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["result"]["ethereum"]["address"], user.evm_addr.as_str());
+        assert_eq!(body["result"]["ethereum"]["in_game_balance"], 2.0);
+    }
+
+    #[tokio::test]
+    async fn test_logout_revokes_token() {
+        let state = Arc::new(AppState::default().await);
+        let app = Router::new()
+            .merge(router(state.clone()).await.layer(crate::auth::AuthLayer {
+                expected_secret: crate::mines::generate_seed(),
+                jwt_secret: state.jwt_secret.clone(),
+                revoked_tokens: state.revoked_tokens.clone(),
+            }))
+            .merge(public_router(state.clone()).await);
+        let username = format!("user_{}", uuid::Uuid::new_v4());
+        assert_eq!(post_register(app.clone(), &username, "pass").await, StatusCode::OK);
+
+        let response = post_credentials(app.clone(), "/auth/login", &username, "pass").await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let token = body["result"]["token"].as_str().unwrap().to_string();
+
+        let send_authed = |method: Method, uri: &str| {
+            let request = Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("Authorization", format!("Bearer {}", token))
+                .body(Body::empty())
+                .unwrap();
+            app.clone().oneshot(request)
+        };
+
+        let before = send_authed(Method::GET, "/user").await.unwrap();
+        assert_eq!(before.status(), StatusCode::OK);
+        let logout = send_authed(Method::POST, "/auth/logout").await.unwrap();
+        assert_eq!(logout.status(), StatusCode::OK);
+        let after = send_authed(Method::GET, "/user").await.unwrap();
+        assert_eq!(after.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_change_password_replaces_credentials_and_tokens() {
+        let state = Arc::new(AppState::default().await);
+        let app = Router::new()
+            .merge(router(state.clone()).await.layer(crate::auth::AuthLayer {
+                expected_secret: crate::mines::generate_seed(),
+                jwt_secret: state.jwt_secret.clone(),
+                revoked_tokens: state.revoked_tokens.clone(),
+            }))
+            .merge(public_router(state.clone()).await);
+        let username = format!("user_{}", uuid::Uuid::new_v4());
+        assert_eq!(post_register(app.clone(), &username, "old").await, StatusCode::OK);
+        let response = post_credentials(app.clone(), "/auth/login", &username, "old").await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let login: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let token = login["result"]["token"].as_str().unwrap().to_string();
+
+        let change = |old_pass: &str| {
+            let request = Request::builder()
+                .method(Method::POST)
+                .uri("/auth/change-password")
+                .header("Authorization", format!("Bearer {}", token))
+                .header("Content-Type", "application/json")
+                .body(Body::from(serde_json::to_vec(&serde_json::json!({ "old_pass": old_pass, "new_pass": "new" })).unwrap()))
+                .unwrap();
+            app.clone().oneshot(request)
+        };
+        assert_eq!(change("wrong").await.unwrap().status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(change("old").await.unwrap().status(), StatusCode::OK);
+
+        let login_status = |pass: &'static str| {
+            let (app, username) = (app.clone(), username.clone());
+            async move { post_credentials(app, "/auth/login", &username, pass).await.status() }
+        };
+        assert_eq!(login_status("old").await, StatusCode::UNAUTHORIZED);
+        assert_eq!(login_status("new").await, StatusCode::OK);
+
+        // Tokens issued under the old password no longer work
+        assert_eq!(change("new").await.unwrap().status(), StatusCode::UNAUTHORIZED);
+        let refresh_token = login["result"]["refresh_token"].clone();
+        let (status, _) = post_json(app, "/auth/refresh", serde_json::json!({ "refresh_token": refresh_token })).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    async fn post_json(app: Router, uri: &str, body: serde_json::Value) -> (StatusCode, serde_json::Value) {
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(uri)
+            .header("Content-Type", "application/json")
+            .body(Body::from(serde_json::to_vec(&body).unwrap()))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(serde_json::Value::Null))
+    }
+
+    #[tokio::test]
+    async fn test_siwe_authenticates_signer_and_rejects_tampering() {
+        use alloy::signers::{Signer, local::PrivateKeySigner};
+
+        let mut state = AppState::default().await;
+        state.siwe_domain = "app.example.com".to_string();
+        state.siwe_chain_id = 421614;
+        let state = Arc::new(state);
+        let app = public_router(state.clone()).await;
+        let signer = PrivateKeySigner::random();
+
+        let request = Request::builder().uri("/auth/siwe/nonce").body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let nonce = body["result"]["nonce"].as_str().unwrap().to_string();
+
+        let message = format!(
+            "app.example.com wants you to sign in with your Ethereum account:\n{}\n\n\
+             Sign in to Choose Rich\n\n\
+             URI: https://app.example.com\nVersion: 1\nChain ID: 421614\nNonce: {}\n\
+             Issued At: 2025-01-01T00:00:00Z",
+            signer.address(),
+            nonce
+        );
+        let signature = signer.sign_message(message.as_bytes()).await.unwrap().to_string();
+
+        // A signature over different text recovers to some other address
+        let tampered = message.replace("Sign in to Choose Rich", "Transfer everything");
+        let (status, _) = post_json(
+            app.clone(),
+            "/auth/siwe/verify",
+            serde_json::json!({ "message": tampered, "signature": signature }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        // Correctly signed, but for a phishing site or another chain
+        for (from, to) in [("app.example.com wants", "evil.example wants"), ("Chain ID: 421614", "Chain ID: 1")] {
+            let elsewhere = message.replace(from, to);
+            let signature = signer.sign_message(elsewhere.as_bytes()).await.unwrap().to_string();
+            let (status, _) = post_json(
+                app.clone(),
+                "/auth/siwe/verify",
+                serde_json::json!({ "message": elsewhere, "signature": signature }),
+            )
+            .await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED);
+        }
+
+        let (status, body) = post_json(
+            app.clone(),
+            "/auth/siwe/verify",
+            serde_json::json!({ "message": message, "signature": signature }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let sub = crate::auth::decode_jwt(body["result"]["token"].as_str().unwrap(), &state.jwt_secret)
+            .unwrap()
+            .sub;
+        let user = state
+            .store
+            .get_user_by_wallet_addr(&signer.address().to_checksum(None))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(sub, user.user_id);
+
+        // The nonce was consumed, so the same signed message can't be replayed
+        let (status, _) = post_json(
+            app,
+            "/auth/siwe/verify",
+            serde_json::json!({ "message": message, "signature": signature }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    async fn post_refresh(app: Router, refresh_token: &str) -> (StatusCode, serde_json::Value) {
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/auth/refresh")
+            .header("Content-Type", "application/json")
+            .body(Body::from(
+                serde_json::to_vec(&serde_json::json!({ "refresh_token": refresh_token })).unwrap(),
+            ))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(serde_json::Value::Null))
+    }
+
+    #[tokio::test]
+    async fn test_refresh_rotates_token_and_rejects_reuse() {
+        let state = Arc::new(AppState::default().await);
+        let app = public_router(state.clone()).await;
+        let username = format!("user_{}", uuid::Uuid::new_v4());
+        assert_eq!(post_register(app.clone(), &username, "pass").await, StatusCode::OK);
+
+        let response = post_credentials(app.clone(), "/auth/login", &username, "pass").await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let first = body["result"]["refresh_token"].as_str().unwrap().to_string();
+
+        let (status, body) = post_refresh(app.clone(), &first).await;
+        assert_eq!(status, StatusCode::OK);
+        let user = state.store.get_user_by_username(&username).await.unwrap().unwrap();
+        let sub = crate::auth::decode_jwt(body["result"]["token"].as_str().unwrap(), &state.jwt_secret).unwrap().sub;
+        assert_eq!(sub, user.user_id);
+        let second = body["result"]["refresh_token"].as_str().unwrap().to_string();
+        assert_ne!(first, second);
+
+        // The rotated token is spent; its replacement still works once
+        let (status, _) = post_refresh(app.clone(), &first).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = post_refresh(app.clone(), &second).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = post_refresh(app, "not-a-token").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+}
@@ -0,0 +1,118 @@
+use alloy::primitives::{Address, Signature};
+use sqlx::types::chrono::{DateTime, Utc};
+use std::str::FromStr;
+
+use crate::auth::AuthError;
+
+/// The parts of an EIP-4361 (Sign-In with Ethereum) message the server checks
+#[derive(Debug, Clone)]
+pub struct SiweMessage {
+    pub domain: String,
+    pub address: Address,
+    pub nonce: String,
+    pub chain_id: u64,
+    pub expiration_time: Option<DateTime<Utc>>,
+}
+
+const PREAMBLE_SUFFIX: &str = " wants you to sign in with your Ethereum account:";
+
+impl SiweMessage {
+    /// Parses the fixed EIP-4361 layout: a preamble line, the address line, an optional
+    /// statement and then `Key: value` fields
+    pub fn parse(message: &str) -> Result<Self, AuthError> {
+        let invalid = |reason: &str| AuthError::SignatureVerificationFailed(format!("Invalid SIWE message: {}", reason));
+        let mut lines = message.lines();
+
+        let domain = lines
+            .next()
+            .and_then(|line| line.strip_suffix(PREAMBLE_SUFFIX))
+            .filter(|domain| !domain.is_empty())
+            .ok_or_else(|| invalid("missing preamble"))?
+            .to_string();
+        let address = lines
+            .next()
+            .and_then(|line| Address::from_str(line.trim()).ok())
+            .ok_or_else(|| invalid("missing address"))?;
+
+        let field = |name: &str| {
+            message
+                .lines()
+                .find_map(|line| line.strip_prefix(name)?.strip_prefix(": "))
+                .map(str::trim)
+        };
+
+        if field("Version") != Some("1") {
+            return Err(invalid("unsupported version"));
+        }
+        let nonce = field("Nonce")
+            .filter(|nonce| nonce.len() >= 8 && nonce.chars().all(|c| c.is_ascii_alphanumeric()))
+            .ok_or_else(|| invalid("missing nonce"))?
+            .to_string();
+        let chain_id = field("Chain ID")
+            .and_then(|id| id.parse().ok())
+            .ok_or_else(|| invalid("missing chain id"))?;
+        let expiration_time = field("Expiration Time")
+            .map(|time| {
+                DateTime::parse_from_rfc3339(time)
+                    .map(|time| time.with_timezone(&Utc))
+                    .map_err(|_| invalid("bad expiration time"))
+            })
+            .transpose()?;
+
+        Ok(Self {
+            domain,
+            address,
+            nonce,
+            chain_id,
+            expiration_time,
+        })
+    }
+
+    /// Checks the message hasn't expired and was signed (EIP-191) by its own address
+    pub fn verify(&self, message: &str, signature: &str) -> Result<(), AuthError> {
+        if self.expiration_time.is_some_and(|expires| expires <= Utc::now()) {
+            return Err(AuthError::SignatureVerificationFailed(
+                "SIWE message has expired".to_string(),
+            ));
+        }
+
+        let signature = Signature::from_str(signature)
+            .map_err(|e| AuthError::SignatureVerificationFailed(format!("Invalid signature: {}", e)))?;
+        let signer = signature
+            .recover_address_from_msg(message.as_bytes())
+            .map_err(|e| AuthError::SignatureVerificationFailed(format!("Invalid signature: {}", e)))?;
+
+        if signer != self.address {
+            return Err(AuthError::SignatureVerificationFailed(
+                "Signature does not match address".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_reads_required_fields() {
+        let message = "app.example.com wants you to sign in with your Ethereum account:\n\
+            0x000000000000000000000000000000000000dEaD\n\n\
+            Sign in to Choose Rich\n\n\
+            URI: https://app.example.com\n\
+            Version: 1\n\
+            Chain ID: 421614\n\
+            Nonce: abc123def456\n\
+            Issued At: 2025-01-01T00:00:00Z";
+
+        let parsed = SiweMessage::parse(message).unwrap();
+        assert_eq!(parsed.domain, "app.example.com");
+        assert_eq!(parsed.nonce, "abc123def456");
+        assert_eq!(parsed.chain_id, 421614);
+        assert!(parsed.expiration_time.is_none());
+
+        assert!(SiweMessage::parse(&message.replace("Version: 1", "Version: 2")).is_err());
+        assert!(SiweMessage::parse(&message.replace("Nonce: abc123def456", "Nonce: x")).is_err());
+    }
+}
@@ -1,44 +1,94 @@
 use crate::{
     deposit_monitor::{
         DepositEvent, DepositMonitorConfig, DepositResult, FailedDeposit, MonitoredAddress,
-        PendingDeposit, ProcessedDeposit, SimulationState,
+        PendingDeposit, ProcessedDeposit, SimulationState, TokenConfig,
     },
-    store::{GameTransaction, Store},
+    metrics::Metrics,
+    primitives::Amount,
+    store::Store,
 };
 use alloy::{
     network::EthereumWallet,
+    primitives::{Address, U256, utils::{format_ether, format_units}},
     providers::{Provider, ProviderBuilder},
     rpc::types::{Block, Filter, Log, TransactionReceipt},
+    sol_types::SolEvent,
     transports::http::{Client, Http},
 };
+use bigdecimal::ToPrimitive;
 use rand::Rng;
 use sqlx::{types::BigDecimal, Row};
 use std::{
     collections::HashMap,
     str::FromStr,
-    sync::{Arc, Mutex},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
     time::Duration,
 };
-use tokio::time;
+use tokio::{sync::Notify, task::JoinHandle, time};
 use tracing::{debug, error, info, warn};
 
+alloy::sol! {
+    event Transfer(address indexed from, address indexed to, uint256 value);
+}
+
+// Decode an ERC-20 Transfer log into (from, to, amount) using the token's decimals
+fn decode_transfer_log(
+    log: &Log,
+    decimals: u8,
+) -> Result<(Address, Address, BigDecimal), Box<dyn std::error::Error + Send + Sync>> {
+    let transfer = log.log_decode::<Transfer>()?.inner.data;
+    let amount = BigDecimal::from_str(&format_units(transfer.value, decimals)?)?;
+    Ok((transfer.from, transfer.to, amount))
+}
+
 pub struct DepositMonitor {
     store: Arc<Store>,
     config: DepositMonitorConfig,
     simulation_state: Arc<Mutex<SimulationState>>,
     is_running: Arc<Mutex<bool>>,
+    paused: Arc<AtomicBool>, // While set the loop keeps ticking but skips deposit checks
+    shutdown: Arc<Notify>, // Wakes the background loop so stop() doesn't wait out an interval
+    task: Arc<Mutex<Option<JoinHandle<()>>>>,
+    metrics: Arc<Metrics>,
 }
 
 impl DepositMonitor {
     pub fn new(store: Arc<Store>, config: DepositMonitorConfig) -> Self {
         Self {
             store,
+            simulation_state: Arc::new(Mutex::new(SimulationState::new(config.seed))),
             config,
-            simulation_state: Arc::new(Mutex::new(SimulationState::default())),
             is_running: Arc::new(Mutex::new(false)),
+            paused: Arc::new(AtomicBool::new(false)),
+            shutdown: Arc::new(Notify::new()),
+            task: Arc::new(Mutex::new(None)),
+            metrics: Arc::new(Metrics::new()),
         }
     }
 
+    // Report deposits to a shared registry instead of a private one
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::SeqCst);
+        info!("Deposit monitor paused");
+    }
+
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::SeqCst);
+        info!("Deposit monitor resumed");
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
     pub async fn start(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         {
             let mut running = self.is_running.lock().unwrap();
@@ -58,8 +108,12 @@ impl DepositMonitor {
         let config = self.config.clone();
         let simulation_state = Arc::clone(&self.simulation_state);
         let is_running = Arc::clone(&self.is_running);
+        let paused = Arc::clone(&self.paused);
+        let shutdown = Arc::clone(&self.shutdown);
+        let task = Arc::clone(&self.task);
+        let metrics = Arc::clone(&self.metrics);
 
-        tokio::spawn(async move {
+        let handle = tokio::spawn(async move {
             let mut interval = time::interval(Duration::from_secs(config.check_interval_secs));
 
             loop {
@@ -71,13 +125,24 @@ impl DepositMonitor {
                     }
                 }
 
-                interval.tick().await;
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    _ = shutdown.notified() => break,
+                }
+                if paused.load(Ordering::SeqCst) {
+                    debug!("Deposit monitor paused, skipping check");
+                    continue;
+                }
 
                 let monitor = DepositMonitor {
                     store: Arc::clone(&store),
                     config: config.clone(),
                     simulation_state: Arc::clone(&simulation_state),
                     is_running: Arc::clone(&is_running),
+                    paused: Arc::clone(&paused),
+                    shutdown: Arc::clone(&shutdown),
+                    task: Arc::clone(&task),
+                    metrics: Arc::clone(&metrics),
                 };
 
                 match monitor.check_deposits().await {
@@ -120,14 +185,26 @@ impl DepositMonitor {
 
             info!("Deposit monitor stopped");
         });
+        *self.task.lock().unwrap() = Some(handle);
 
         Ok(())
     }
 
+    // Stop the background loop and wait for any in-flight check to finish
     pub async fn stop(&self) {
-        let mut running = self.is_running.lock().unwrap();
-        *running = false;
+        {
+            let mut running = self.is_running.lock().unwrap();
+            *running = false;
+        }
         info!("Deposit monitor stop requested");
+        self.shutdown.notify_one();
+
+        let handle = self.task.lock().unwrap().take();
+        if let Some(handle) = handle {
+            if let Err(e) = handle.await {
+                error!("Deposit monitor task failed: {}", e);
+            }
+        }
     }
 
     pub async fn check_deposits(&self) -> Result<DepositResult, Box<dyn std::error::Error + Send + Sync>> {
@@ -140,24 +217,83 @@ impl DepositMonitor {
         let mut processed_deposits = Vec::new();
         let mut failed_deposits = Vec::new();
 
-        if self.config.enable_simulation {
+        let (deposits, head_block) = if self.config.enable_simulation {
             // Use simulation mode for development/testing
-            let deposits = self.simulate_deposits(&monitored_addresses).await?;
-            debug!("Simulated {} deposits", deposits.len());
+            let simulated = self.simulate_deposits(&monitored_addresses).await?;
+            debug!("Simulated {} deposits", simulated.len());
 
-            for deposit in deposits {
-                match self.process_deposit(deposit).await {
-                    Ok(processed) => processed_deposits.push(processed),
-                    Err(e) => {
-                        error!("Failed to process simulated deposit: {}", e);
-                        // We don't have enough info to create FailedDeposit here
-                    }
+            // The in-memory cache is lost on restart, so also check what was already credited
+            let mut deposits = Vec::new();
+            for deposit in simulated {
+                if !self.store.is_transaction_processed(&deposit.transaction_hash).await? {
+                    deposits.push(deposit);
                 }
             }
+
+            let state = self.simulation_state.lock().unwrap();
+            (deposits, state.current_block)
         } else {
-            // Real blockchain monitoring (placeholder for now)
-            warn!("Real blockchain monitoring not yet implemented - using simulation");
-            // TODO: Implement real blockchain monitoring
+            let (deposits, head_block) = self.scan_deposits(&monitored_addresses).await?;
+            debug!("Found {} on-chain deposits up to block {}", deposits.len(), head_block);
+            (deposits, head_block)
+        };
+
+        // Dust isn't worth accounting for. A native deposit below the minimum stays in the
+        // balance delta, so it's credited once later deposits push the total over it
+        let deposits = deposits
+            .into_iter()
+            .filter(|deposit| {
+                let keep = deposit.amount >= self.config.min_deposit;
+                if !keep {
+                    info!(
+                        "Ignoring deposit of {} to {} below minimum {}",
+                        deposit.amount, deposit.to_address, self.config.min_deposit
+                    );
+                }
+                keep
+            })
+            .collect();
+
+        // New deposits wait until they have enough confirmations before being credited
+        self.queue_pending_deposits(deposits, &monitored_addresses);
+
+        for pending in self.take_confirmed_deposits(head_block) {
+            let deposit = DepositEvent {
+                from_address: String::new(),
+                to_address: pending.game_address.clone(),
+                amount: pending.amount.clone(),
+                transaction_hash: pending.transaction_hash.clone(),
+                block_number: pending.block_number,
+                timestamp: chrono::Utc::now().timestamp(),
+                token: pending.token.clone(),
+            };
+
+            match self.process_deposit(deposit).await {
+                Ok(processed) => {
+                    // Simulated deposits never touch the chain, and token deposits
+                    // don't change the native balance
+                    if !self.config.enable_simulation && pending.token.is_none() {
+                        self.store
+                            .adjust_on_chain_last_seen(&processed.game_address, &processed.amount)
+                            .await?;
+                    }
+                    processed_deposits.push(processed)
+                }
+                Err(e) => failed_deposits.push(FailedDeposit {
+                    user_id: pending.user_id,
+                    game_address: pending.game_address,
+                    amount: pending.amount,
+                    transaction_hash: pending.transaction_hash,
+                    error: e.to_string(),
+                }),
+            }
+        }
+
+        // Persist progress so the next cycle (or a restart) resumes from here
+        for address in &monitored_addresses {
+            self.store
+                .set_last_checked_block(&address.game_address, head_block)
+                .await?;
         }
 
         Ok(DepositResult {
@@ -167,20 +303,28 @@ impl DepositMonitor {
     }
 
     async fn get_monitored_addresses(&self) -> Result<Vec<MonitoredAddress>, Box<dyn std::error::Error + Send + Sync>> {
-        // Query database for all user game addresses
-        let users = sqlx::query("SELECT user_id, evm_addr FROM users WHERE evm_addr IS NOT NULL")
-            .fetch_all(self.store.pool())
-            .await?;
+        // Query database for all user game addresses along with their scan progress
+        let users = sqlx::query(
+            r#"
+            SELECT u.user_id, u.evm_addr, COALESCE(m.last_checked_block, 0) AS last_checked_block
+            FROM users u
+            LEFT JOIN monitored_addresses m ON m.game_address = u.evm_addr
+            WHERE u.evm_addr IS NOT NULL AND u.deleted_at IS NULL
+            "#,
+        )
+        .fetch_all(self.store.pool())
+        .await?;
 
         let addresses = users
             .into_iter()
             .filter_map(|row| {
                 let user_id: String = row.try_get("user_id").ok()?;
                 let evm_addr: String = row.try_get("evm_addr").ok()?;
+                let last_checked_block: i64 = row.try_get("last_checked_block").ok()?;
                 Some(MonitoredAddress {
                     user_id,
                     game_address: evm_addr,
-                    last_checked_block: 0, // Will be tracked separately in real implementation
+                    last_checked_block: last_checked_block as u64,
                 })
             })
             .collect();
@@ -188,63 +332,209 @@ impl DepositMonitor {
         Ok(addresses)
     }
 
-    async fn simulate_deposits(
+    fn queue_pending_deposits(&self, deposits: Vec<DepositEvent>, addresses: &[MonitoredAddress]) {
+        let mut state = self.simulation_state.lock().unwrap();
+        for deposit in deposits {
+            let Some(address) = addresses.iter().find(|a| a.game_address == deposit.to_address) else {
+                warn!("Ignoring deposit to unmonitored address {}", deposit.to_address);
+                continue;
+            };
+
+            debug!(
+                "Holding deposit {} at block {} until confirmed",
+                deposit.transaction_hash, deposit.block_number
+            );
+            state
+                .pending_deposits
+                .entry(deposit.to_address.clone())
+                .or_default()
+                .push(PendingDeposit {
+                    user_id: address.user_id.clone(),
+                    game_address: deposit.to_address,
+                    amount: deposit.amount,
+                    transaction_hash: deposit.transaction_hash,
+                    block_number: deposit.block_number,
+                    confirmation_count: 0,
+                    token: deposit.token,
+                });
+        }
+    }
+
+    // Remove and return pending deposits that have reached the required confirmations
+    fn take_confirmed_deposits(&self, head_block: u64) -> Vec<PendingDeposit> {
+        let required = self.config.required_confirmations as u64;
+        let mut confirmed = Vec::new();
+        let mut state = self.simulation_state.lock().unwrap();
+
+        for pending in state.pending_deposits.values_mut() {
+            for deposit in pending.iter_mut() {
+                deposit.confirmation_count = head_block.saturating_sub(deposit.block_number) as u32;
+            }
+            let (ready, waiting): (Vec<_>, Vec<_>) = pending
+                .drain(..)
+                .partition(|d| d.confirmation_count as u64 >= required);
+            confirmed.extend(ready);
+            *pending = waiting;
+        }
+        state.pending_deposits.retain(|_, pending| !pending.is_empty());
+
+        confirmed
+    }
+
+    // Compare each address's on-chain balance at the head block against the
+    // last balance we accounted for, and report any increase as a deposit
+    async fn scan_deposits(
         &self,
         addresses: &[MonitoredAddress],
+    ) -> Result<(Vec<DepositEvent>, u64), Box<dyn std::error::Error + Send + Sync>> {
+        let provider = ProviderBuilder::new().connect_http(self.config.rpc_url.parse()?);
+
+        let head_block = provider.get_block_number().await?;
+        let mut deposits = Vec::new();
+
+        for monitored in addresses {
+            let address: Address = match monitored.game_address.parse() {
+                Ok(address) => address,
+                Err(e) => {
+                    warn!("Skipping invalid game address {}: {}", monitored.game_address, e);
+                    continue;
+                }
+            };
+
+            let balance_wei: U256 = provider.get_balance(address).number(head_block).await?;
+            let current_balance = BigDecimal::from_str(&format_ether(balance_wei))?;
+
+            let Some(user) = self.store.get_user_by_evm_addr(&monitored.game_address).await? else {
+                continue;
+            };
+            let last_seen = self.store.get_on_chain_last_seen(&monitored.game_address).await?;
+
+            // Deposits still waiting for confirmations are already accounted for
+            let pending_amount = {
+                let state = self.simulation_state.lock().unwrap();
+                state
+                    .pending_deposits
+                    .get(&monitored.game_address)
+                    .map(|pending| pending.iter().filter(|d| d.token.is_none()).map(|d| &d.amount).sum())
+                    .unwrap_or_else(|| BigDecimal::from(0))
+            };
+
+            let balance_difference = &current_balance - &last_seen - pending_amount;
+            if balance_difference > BigDecimal::from(0) {
+                deposits.push(DepositEvent {
+                    from_address: user.original_wallet_addr.unwrap_or_default(),
+                    to_address: monitored.game_address.clone(),
+                    amount: balance_difference,
+                    // Balance deltas have no single transaction, so key them by address and block
+                    transaction_hash: format!("{}:{}", monitored.game_address, head_block),
+                    block_number: head_block,
+                    timestamp: chrono::Utc::now().timestamp(),
+                    token: None,
+                });
+            }
+        }
+
+        if let Some(token) = &self.config.token {
+            deposits.extend(self.scan_token_transfers(&provider, token, addresses, head_block).await?);
+        }
+
+        Ok((deposits, head_block))
+    }
+
+    // Find ERC-20 Transfer logs into game addresses since they were last checked
+    async fn scan_token_transfers(
+        &self,
+        provider: &impl Provider,
+        token: &TokenConfig,
+        addresses: &[MonitoredAddress],
+        head_block: u64,
     ) -> Result<Vec<DepositEvent>, Box<dyn std::error::Error + Send + Sync>> {
+        let watched: HashMap<Address, &MonitoredAddress> = addresses
+            .iter()
+            .filter_map(|a| Some((a.game_address.parse().ok()?, a)))
+            .collect();
+        let from_block = watched.values().map(|a| a.last_checked_block + 1).min();
+        let Some(from_block) = from_block.filter(|b| *b <= head_block) else {
+            return Ok(Vec::new());
+        };
+
+        let filter = Filter::new()
+            .address(token.address.parse::<Address>()?)
+            .event_signature(Transfer::SIGNATURE_HASH)
+            .topic2(watched.keys().map(|a| a.into_word()).collect::<Vec<_>>())
+            .from_block(from_block)
+            .to_block(head_block);
+
         let mut deposits = Vec::new();
-        let mut rng = rand::thread_rng();
+        for log in provider.get_logs(&filter).await? {
+            let (from, to, amount) = decode_transfer_log(&log, token.decimals)?;
+            let block_number = log.block_number.unwrap_or(head_block);
+            // Logs at or below an address's checkpoint were handled in an earlier cycle
+            let Some(monitored) = watched.get(&to).filter(|a| block_number > a.last_checked_block) else {
+                continue;
+            };
 
-        // Update simulation state
-        {
-            let mut state = self.simulation_state.lock().unwrap();
-            state.current_block += 1;
+            deposits.push(DepositEvent {
+                from_address: format!("{:#x}", from),
+                to_address: monitored.game_address.clone(),
+                amount,
+                transaction_hash: format!(
+                    "{:#x}:{}",
+                    log.transaction_hash.unwrap_or_default(),
+                    log.log_index.unwrap_or_default()
+                ),
+                block_number,
+                timestamp: chrono::Utc::now().timestamp(),
+                token: Some(token.symbol.clone()),
+            });
         }
 
+        Ok(deposits)
+    }
+
+    async fn simulate_deposits(
+        &self,
+        addresses: &[MonitoredAddress],
+    ) -> Result<Vec<DepositEvent>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut deposits = Vec::new();
+        // One lock for the whole cycle so the rng sequence isn't interleaved with other callers
+        let mut state = self.simulation_state.lock().unwrap();
+        state.current_block += 1;
+        let current_block = state.current_block;
+
         // For each address, randomly generate deposits based on probability
         for address in addresses {
-            if rng.r#gen::<f64>() < self.config.simulation_probability {
+            if state.rng.r#gen::<f64>() < self.config.simulation_probability {
                 // Generate a random deposit amount between 0.001 and 10 ETH (in Wei-like units)
-                let amount_eth = rng.gen_range(0.001..10.0);
-                let amount = BigDecimal::from_str(&amount_eth.to_string())?;
+                let amount_eth = state.rng.gen_range(0.001..10.0);
+                let amount = Amount::try_from(amount_eth)?.into_inner();
 
                 // Generate a fake transaction hash
                 let tx_hash = format!(
                     "0x{:064x}",
-                    rng.r#gen::<u64>() as u128 * rng.r#gen::<u64>() as u128
+                    state.rng.r#gen::<u64>() as u128 * state.rng.r#gen::<u64>() as u128
                 );
 
-                let current_block = {
-                    let state = self.simulation_state.lock().unwrap();
-                    state.current_block
-                };
-
                 // Check if we've already processed this transaction
-                let processed = {
-                    let state = self.simulation_state.lock().unwrap();
-                    state.processed_transactions.contains_key(&tx_hash)
-                };
-
-                if !processed {
+                if !state.processed_transactions.contains_key(&tx_hash) {
                     let deposit = DepositEvent {
-                        from_address: format!("0x{:040x}", rng.r#gen::<u128>()),
+                        from_address: format!("0x{:040x}", state.rng.r#gen::<u128>()),
                         to_address: address.game_address.clone(),
                         amount,
                         transaction_hash: tx_hash.clone(),
                         block_number: current_block,
                         timestamp: chrono::Utc::now().timestamp(),
+                        token: None,
                     };
 
                     deposits.push(deposit);
 
                     // Mark as processed in simulation state
-                    {
-                        let mut state = self.simulation_state.lock().unwrap();
-                        state.processed_transactions.insert(tx_hash, true);
-                    }
+                    state.processed_transactions.insert(tx_hash, true);
                 }
             }
         }
+        drop(state);
 
         if !deposits.is_empty() {
             debug!("Simulated {} deposits", deposits.len());
@@ -261,6 +551,10 @@ impl DepositMonitor {
             "Processing deposit: {} to {} (tx: {})",
             deposit.amount, deposit.to_address, deposit.transaction_hash
         );
+        if deposit.amount < self.config.min_deposit {
+            info!("Ignoring deposit of {} below minimum {}", deposit.amount, self.config.min_deposit);
+            return Err(format!("Deposit below minimum of {}", self.config.min_deposit).into());
+        }
 
         // Get user by game address
         let user = self
@@ -269,28 +563,22 @@ impl DepositMonitor {
             .await?
             .ok_or_else(|| format!("User not found for address: {}", deposit.to_address))?;
 
-        // Update user balance - deposit adds to both account and in-game balance
-        let updated_user = self
-            .store
-            .process_deposit(&user.user_id, &deposit.amount)
-            .await?;
-
-        // Record transaction
-        let transaction = GameTransaction {
-            id: String::new(),
-            user_id: user.user_id.clone(),
-            transaction_type: "deposit".to_string(),
-            amount: deposit.amount.clone(),
-            game_type: None,
-            game_session_id: None,
-            description: Some(format!(
-                "Deposit from blockchain - tx: {}",
-                deposit.transaction_hash
-            )),
-            created_at: None,
+        // Update user balance - deposit adds to both account and in-game balance. The hash
+        // and ledger entry are recorded in the same DB transaction so restarts can't credit it twice
+        let description = match &deposit.token {
+            Some(token) => format!("{} deposit from blockchain - tx: {}", token, deposit.transaction_hash),
+            None => format!("Deposit from blockchain - tx: {}", deposit.transaction_hash),
         };
+        let (updated_user, recorded_transaction) = self
+            .store
+            .process_deposit_once(&user.user_id, &deposit.amount, &deposit.transaction_hash, &description)
+            .await?
+            .ok_or_else(|| format!("Deposit already processed: {}", deposit.transaction_hash))?;
 
-        let recorded_transaction = self.store.create_transaction(&transaction).await?;
+        self.metrics.deposits.inc();
+        self.metrics
+            .deposit_volume
+            .inc_by(deposit.amount.to_f64().unwrap_or(0.0));
 
         info!(
             "Successfully processed deposit of {} for user {} to address {} - new account balance: {}, new in-game balance: {}",
@@ -316,6 +604,7 @@ impl DepositMonitor {
         };
 
         status.insert("is_running".to_string(), serde_json::json!(is_running));
+        status.insert("is_paused".to_string(), serde_json::json!(self.is_paused()));
         status.insert(
             "check_interval_secs".to_string(),
             serde_json::json!(self.config.check_interval_secs),
@@ -368,27 +657,400 @@ impl DepositMonitor {
             .fetch_one(self.store.pool())
             .await?;
 
-        let mut rng = rand::thread_rng();
-        let tx_hash = format!(
-            "0x{:064x}",
-            rng.r#gen::<u64>() as u128 * rng.r#gen::<u64>() as u128
-        );
-
-        let current_block = {
+        let (tx_hash, from_address, current_block) = {
             let mut state = self.simulation_state.lock().unwrap();
+            let tx_hash = format!(
+                "0x{:064x}",
+                state.rng.r#gen::<u64>() as u128 * state.rng.r#gen::<u64>() as u128
+            );
+            let from_address = format!("0x{:040x}", state.rng.r#gen::<u128>());
             state.current_block += 1;
-            state.current_block
+            (tx_hash, from_address, state.current_block)
         };
 
         let deposit = DepositEvent {
-            from_address: format!("0x{:040x}", rng.r#gen::<u128>()),
+            from_address,
             to_address: user.try_get("evm_addr")?,
             amount,
             transaction_hash: tx_hash,
             block_number: current_block,
             timestamp: chrono::Utc::now().timestamp(),
+            token: None,
         };
 
         self.process_deposit(deposit).await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{server::AppState, store::User};
+
+    async fn create_test_user(store: &Store) -> User {
+        let user = User::new(
+            String::new(),
+            format!("monitor_test_{}", uuid::Uuid::new_v4()),
+            String::new(),
+            String::new(),
+            format!("0x{}", uuid::Uuid::new_v4().simple()),
+            None,
+            BigDecimal::from(1),
+            BigDecimal::from(2),
+        );
+        store.create_user(&user).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_stop_terminates_monitor_loop() {
+        let state = AppState::default().await;
+        let config = DepositMonitorConfig {
+            check_interval_secs: 3600,
+            enable_simulation: true,
+            simulation_probability: 0.0,
+            ..DepositMonitorConfig::default()
+        };
+        let monitor = DepositMonitor::new(state.store.clone(), config);
+
+        monitor.start().await.unwrap();
+        assert!(*monitor.is_running.lock().unwrap());
+
+        // The loop is parked on an hour-long interval; stop must still end it promptly
+        tokio::time::timeout(Duration::from_secs(10), monitor.stop())
+            .await
+            .expect("monitor loop did not exit");
+        assert!(!*monitor.is_running.lock().unwrap());
+        assert!(monitor.task.lock().unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_paused_monitor_processes_no_deposits() {
+        let state = AppState::default().await;
+        let config = DepositMonitorConfig {
+            check_interval_secs: 1,
+            enable_simulation: true,
+            simulation_probability: 1.0,
+            ..DepositMonitorConfig::default()
+        };
+        let monitor = DepositMonitor::new(state.store.clone(), config);
+        monitor.pause();
+        assert_eq!(monitor.get_status().await["is_paused"], serde_json::json!(true));
+
+        // The first tick fires immediately and a second follows a second later
+        monitor.start().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        monitor.stop().await;
+
+        // Every check cycle advances the simulated chain, so none ran
+        assert_eq!(monitor.simulation_state.lock().unwrap().current_block, 1000000);
+        assert!(monitor.simulation_state.lock().unwrap().processed_transactions.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_same_seed_simulates_same_deposits() {
+        let state = AppState::default().await;
+        let config = DepositMonitorConfig {
+            enable_simulation: true,
+            simulation_probability: 1.0,
+            seed: Some(42),
+            ..DepositMonitorConfig::default()
+        };
+        let addresses: Vec<MonitoredAddress> = (0..3)
+            .map(|i| MonitoredAddress {
+                user_id: format!("user-{}", i),
+                game_address: format!("0x{:040x}", i),
+                last_checked_block: 0,
+            })
+            .collect();
+
+        let simulate = |monitor: DepositMonitor| {
+            let addresses = addresses.clone();
+            async move {
+                let mut events = monitor.simulate_deposits(&addresses).await.unwrap();
+                events.extend(monitor.simulate_deposits(&addresses).await.unwrap());
+                events
+                    .into_iter()
+                    .map(|e| (e.from_address, e.to_address, e.amount, e.transaction_hash, e.block_number))
+                    .collect::<Vec<_>>()
+            }
+        };
+        let first = simulate(DepositMonitor::new(state.store.clone(), config.clone())).await;
+        let second = simulate(DepositMonitor::new(state.store.clone(), config)).await;
+        assert_eq!(first.len(), 6);
+        assert_eq!(first, second);
+    }
+
+    #[tokio::test]
+    async fn test_force_simulate_deposit_credits_both_balances() {
+        let state = AppState::default().await;
+        let user = create_test_user(&state.store).await;
+        let monitor = DepositMonitor::new(state.store.clone(), DepositMonitorConfig::default());
+
+        let amount = BigDecimal::from_str("0.5").unwrap();
+        let processed = monitor
+            .force_simulate_deposit(&user.user_id, amount.clone())
+            .await
+            .unwrap();
+        assert_eq!(processed.new_balance, &user.account_balance + &amount);
+
+        let updated = state.store.get_user_by_evm_addr(&user.evm_addr).await.unwrap().unwrap();
+        assert_eq!(updated.account_balance, &user.account_balance + &amount);
+        assert_eq!(updated.in_game_balance, &user.in_game_balance + &amount);
+    }
+
+    #[tokio::test]
+    async fn test_deposit_below_minimum_is_not_credited() {
+        let state = AppState::default().await;
+        let user = create_test_user(&state.store).await;
+        let config = DepositMonitorConfig {
+            min_deposit: BigDecimal::from_str("0.01").unwrap(),
+            ..DepositMonitorConfig::default()
+        };
+        let monitor = DepositMonitor::new(state.store.clone(), config);
+
+        let dust = BigDecimal::from_str("0.001").unwrap();
+        assert!(monitor.force_simulate_deposit(&user.user_id, dust).await.is_err());
+        let unchanged = state.store.get_user_by_evm_addr(&user.evm_addr).await.unwrap().unwrap();
+        assert_eq!(unchanged.account_balance, user.account_balance);
+        assert_eq!(unchanged.in_game_balance, user.in_game_balance);
+    }
+
+    #[tokio::test]
+    async fn test_last_checked_block_advances_across_cycles() {
+        let state = AppState::default().await;
+        let user = create_test_user(&state.store).await;
+        let config = DepositMonitorConfig {
+            enable_simulation: true,
+            simulation_probability: 0.0,
+            ..DepositMonitorConfig::default()
+        };
+        let monitor = DepositMonitor::new(state.store.clone(), config);
+        assert_eq!(state.store.get_last_checked_block(&user.evm_addr).await.unwrap(), None);
+
+        monitor.check_deposits().await.unwrap();
+        let first = state.store.get_last_checked_block(&user.evm_addr).await.unwrap().unwrap();
+
+        monitor.check_deposits().await.unwrap();
+        let second = state.store.get_last_checked_block(&user.evm_addr).await.unwrap().unwrap();
+        assert!(second > first);
+
+        let addresses = monitor.get_monitored_addresses().await.unwrap();
+        let address = addresses.iter().find(|a| a.game_address == user.evm_addr).unwrap();
+        assert_eq!(address.last_checked_block, second);
+    }
+
+    #[tokio::test]
+    async fn test_closed_accounts_are_not_monitored() {
+        let state = AppState::default().await;
+        let user = create_test_user(&state.store).await;
+        let monitor = DepositMonitor::new(state.store.clone(), DepositMonitorConfig::default());
+        let watched = || async {
+            monitor
+                .get_monitored_addresses()
+                .await
+                .unwrap()
+                .iter()
+                .any(|a| a.game_address == user.evm_addr)
+        };
+
+        assert!(watched().await);
+        assert!(state.store.close_account(&user.user_id).await.unwrap());
+        assert!(!watched().await);
+    }
+
+    #[tokio::test]
+    async fn test_same_deposit_is_only_credited_once() {
+        let state = AppState::default().await;
+        let user = create_test_user(&state.store).await;
+        let monitor = DepositMonitor::new(state.store.clone(), DepositMonitorConfig::default());
+
+        let deposit = DepositEvent {
+            from_address: String::new(),
+            to_address: user.evm_addr.clone(),
+            amount: BigDecimal::from(2),
+            transaction_hash: format!("0x{}", uuid::Uuid::new_v4().simple()),
+            block_number: 1,
+            timestamp: chrono::Utc::now().timestamp(),
+            token: None,
+        };
+
+        monitor.process_deposit(deposit.clone()).await.unwrap();
+        assert!(monitor.process_deposit(deposit.clone()).await.is_err());
+        assert!(state.store.is_transaction_processed(&deposit.transaction_hash).await.unwrap());
+
+        let updated = state.store.get_user_by_evm_addr(&user.evm_addr).await.unwrap().unwrap();
+        assert_eq!(updated.account_balance, &user.account_balance + BigDecimal::from(2));
+        assert_eq!(updated.in_game_balance, &user.in_game_balance + BigDecimal::from(2));
+    }
+
+    #[tokio::test]
+    async fn test_deposit_waits_for_required_confirmations() {
+        let state = AppState::default().await;
+        let user = create_test_user(&state.store).await;
+        let config = DepositMonitorConfig {
+            required_confirmations: 2,
+            enable_simulation: true,
+            simulation_probability: 0.0,
+            ..DepositMonitorConfig::default()
+        };
+        let monitor = DepositMonitor::new(state.store.clone(), config);
+
+        let head_block = monitor.simulation_state.lock().unwrap().current_block;
+        let addresses = monitor.get_monitored_addresses().await.unwrap();
+        monitor.queue_pending_deposits(
+            vec![DepositEvent {
+                from_address: String::new(),
+                to_address: user.evm_addr.clone(),
+                amount: BigDecimal::from(3),
+                transaction_hash: format!("0x{}", uuid::Uuid::new_v4().simple()),
+                block_number: head_block,
+                timestamp: chrono::Utc::now().timestamp(),
+                token: None,
+            }],
+            &addresses,
+        );
+
+        // One block later the deposit is still held
+        let result = monitor.check_deposits().await.unwrap();
+        assert!(result.processed_deposits.is_empty());
+        let updated = state.store.get_user_by_evm_addr(&user.evm_addr).await.unwrap().unwrap();
+        assert_eq!(updated.account_balance, user.account_balance);
+
+        // Two blocks later it is credited
+        let result = monitor.check_deposits().await.unwrap();
+        assert_eq!(result.processed_deposits.len(), 1);
+        let updated = state.store.get_user_by_evm_addr(&user.evm_addr).await.unwrap().unwrap();
+        assert_eq!(updated.account_balance, &user.account_balance + BigDecimal::from(3));
+        assert!(monitor.simulation_state.lock().unwrap().pending_deposits.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_disabled_simulation_fabricates_no_deposits() {
+        use axum::{Json, Router, routing::post};
+
+        let state = AppState::default().await;
+        let user = create_test_user(&state.store).await;
+
+        // Node with nothing on chain, so any deposit found would have to be made up
+        let app = Router::new().route(
+            "/",
+            post(|Json(req): Json<serde_json::Value>| async move {
+                let result = if req["method"] == "eth_blockNumber" { "0x10" } else { "0x0" };
+                Json(serde_json::json!({ "jsonrpc": "2.0", "id": req["id"], "result": result }))
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        // Simulation is opt-in, however likely it is configured to be
+        let config = DepositMonitorConfig {
+            rpc_url: format!("http://{}", addr),
+            simulation_probability: 1.0,
+            required_confirmations: 0,
+            ..DepositMonitorConfig::default()
+        };
+        assert!(!config.enable_simulation);
+        let monitor = DepositMonitor::new(state.store.clone(), config);
+
+        let result = monitor.check_deposits().await.unwrap();
+        assert!(!result.processed_deposits.iter().any(|d| d.game_address == user.evm_addr));
+        let simulation = monitor.simulation_state.lock().unwrap();
+        assert_eq!(simulation.current_block, 1000000);
+        assert!(simulation.processed_transactions.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_scan_credits_on_chain_balance_increase() {
+        use axum::{Json, Router, routing::post};
+
+        let state = AppState::default().await;
+        let user = User::new(
+            String::new(),
+            format!("monitor_test_{}", uuid::Uuid::new_v4()),
+            String::new(),
+            String::new(),
+            format!("0x{:0>40}", uuid::Uuid::new_v4().simple().to_string()),
+            None,
+            BigDecimal::from(1),
+            BigDecimal::from(1),
+        );
+        let user = state.store.create_user(&user).await.unwrap();
+        // The 1 ETH already credited was last seen on chain
+        state.store.adjust_on_chain_last_seen(&user.evm_addr, &BigDecimal::from(1)).await.unwrap();
+
+        // Minimal JSON-RPC node: head block 16, 1.5 ETH on the test address, nothing elsewhere
+        let game_address = user.evm_addr.to_lowercase();
+        let app = Router::new().route(
+            "/",
+            post(move |Json(req): Json<serde_json::Value>| {
+                let game_address = game_address.clone();
+                async move {
+                    let result = match req["method"].as_str() {
+                        Some("eth_blockNumber") => "0x10",
+                        Some("eth_getBalance")
+                            if req["params"][0].as_str().map(str::to_lowercase)
+                                == Some(game_address) =>
+                        {
+                            "0x14d1120d7b160000"
+                        }
+                        _ => "0x0",
+                    };
+                    Json(serde_json::json!({ "jsonrpc": "2.0", "id": req["id"], "result": result }))
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let config = DepositMonitorConfig {
+            rpc_url: format!("http://{}", addr),
+            enable_simulation: false,
+            required_confirmations: 0,
+            ..DepositMonitorConfig::default()
+        };
+        let monitor = DepositMonitor::new(state.store.clone(), config);
+
+        let result = monitor.check_deposits().await.unwrap();
+        let deposit = result
+            .processed_deposits
+            .iter()
+            .find(|d| d.game_address == user.evm_addr)
+            .unwrap();
+        assert_eq!(deposit.amount, BigDecimal::from_str("0.5").unwrap());
+
+        let updated = state.store.get_user_by_evm_addr(&user.evm_addr).await.unwrap().unwrap();
+        assert_eq!(updated.account_balance, BigDecimal::from_str("1.5").unwrap());
+        assert!(state.store.get_last_checked_block(&user.evm_addr).await.unwrap().unwrap() >= 16);
+
+        // Already credited balance is not counted twice
+        monitor.check_deposits().await.unwrap();
+        let updated = state.store.get_user_by_evm_addr(&user.evm_addr).await.unwrap().unwrap();
+        assert_eq!(updated.account_balance, BigDecimal::from_str("1.5").unwrap());
+    }
+
+    #[test]
+    fn test_decode_transfer_log_uses_token_decimals() {
+        use alloy::primitives::{LogData, address};
+
+        let from = address!("0x1111111111111111111111111111111111111111");
+        let to = address!("0x2222222222222222222222222222222222222222");
+        let data = LogData::new_unchecked(
+            vec![Transfer::SIGNATURE_HASH, from.into_word(), to.into_word()],
+            U256::from(1_500_000u64).to_be_bytes::<32>().to_vec().into(),
+        );
+        let log = Log {
+            inner: alloy::primitives::Log {
+                address: address!("0x3333333333333333333333333333333333333333"),
+                data,
+            },
+            ..Default::default()
+        };
+
+        let (decoded_from, decoded_to, amount) = decode_transfer_log(&log, 6).unwrap();
+        assert_eq!(decoded_from, from);
+        assert_eq!(decoded_to, to);
+        assert_eq!(amount, BigDecimal::from_str("1.5").unwrap());
+    }
+}
@@ -1,6 +1,8 @@
+use crate::wallet::ARB_SEPOLIA_RPC;
+use rand::{SeedableRng, rngs::StdRng};
 use serde::{Deserialize, Serialize};
 use sqlx::types::BigDecimal;
-use std::collections::HashMap;
+use std::{collections::HashMap, str::FromStr};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PendingDeposit {
@@ -10,6 +12,7 @@ pub struct PendingDeposit {
     pub transaction_hash: String,
     pub block_number: u64,
     pub confirmation_count: u32,
+    pub token: Option<String>, // Token symbol, None for native ETH
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -20,6 +23,7 @@ pub struct DepositEvent {
     pub transaction_hash: String,
     pub block_number: u64,
     pub timestamp: i64,
+    pub token: Option<String>, // Token symbol, None for native ETH
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -29,13 +33,24 @@ pub struct MonitoredAddress {
     pub last_checked_block: u64,
 }
 
+// ERC-20 token accepted for deposits alongside native ETH
+#[derive(Debug, Clone)]
+pub struct TokenConfig {
+    pub address: String,
+    pub symbol: String,
+    pub decimals: u8,
+}
+
 #[derive(Debug, Clone)]
 pub struct DepositMonitorConfig {
     pub check_interval_secs: u64,
     pub required_confirmations: u32,
-    pub rpc_url: Option<String>,
-    pub enable_simulation: bool,
+    pub rpc_url: String,
+    pub enable_simulation: bool, // Fabricates deposits that credit real balances, so never on by default
     pub simulation_probability: f64, // Probability of generating a random deposit (0.0 to 1.0)
+    pub token: Option<TokenConfig>,
+    pub seed: Option<u64>, // Makes simulated deposits reproducible; random when unset
+    pub min_deposit: BigDecimal, // Smaller deposits are dust and ignored
 }
 
 impl Default for DepositMonitorConfig {
@@ -43,9 +58,12 @@ impl Default for DepositMonitorConfig {
         Self {
             check_interval_secs: 5,
             required_confirmations: 3,
-            rpc_url: None,
-            enable_simulation: true,
+            rpc_url: ARB_SEPOLIA_RPC.to_string(),
+            enable_simulation: false,
             simulation_probability: 0.01, // 1% chance per check cycle
+            token: None,
+            seed: None,
+            min_deposit: BigDecimal::from_str("0.0001").unwrap(),
         }
     }
 }
@@ -84,14 +102,25 @@ pub struct SimulationState {
     pub pending_deposits: HashMap<String, Vec<PendingDeposit>>,
     pub processed_transactions: ProcessedTransactionCache,
     pub current_block: u64,
+    pub rng: StdRng, // Source of every simulated amount, hash and sender
 }
 
-impl Default for SimulationState {
-    fn default() -> Self {
+impl SimulationState {
+    pub fn new(seed: Option<u64>) -> Self {
         Self {
             pending_deposits: HashMap::new(),
             processed_transactions: HashMap::new(),
             current_block: 1000000, // Start at a reasonable block number
+            rng: match seed {
+                Some(seed) => StdRng::seed_from_u64(seed),
+                None => StdRng::from_entropy(),
+            },
         }
     }
 }
+
+impl Default for SimulationState {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
@@ -0,0 +1,51 @@
+use prometheus::{Counter, IntCounter, IntCounterVec, IntGauge, Opts, Registry, TextEncoder};
+
+// Prometheus collectors for game and deposit activity, exported on /metrics
+pub struct Metrics {
+    registry: Registry,
+    pub games_started: IntCounterVec, // Labelled by game type
+    pub games_won: IntCounterVec,
+    pub games_lost: IntCounterVec,
+    pub deposits: IntCounter,
+    pub deposit_volume: Counter, // Sum of credited deposit amounts
+    pub active_sessions: IntGauge, // Refreshed from the session caches on each scrape
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let game_counter = |name: &str, help: &str| {
+            IntCounterVec::new(Opts::new(name, help), &["game_type"]).unwrap()
+        };
+        let metrics = Self {
+            registry: Registry::new(),
+            games_started: game_counter("games_started_total", "Games started"),
+            games_won: game_counter("games_won_total", "Games that paid out"),
+            games_lost: game_counter("games_lost_total", "Games that lost the stake"),
+            deposits: IntCounter::new("deposits_total", "Deposits credited").unwrap(),
+            deposit_volume: Counter::new("deposit_volume_total", "Total amount deposited").unwrap(),
+            active_sessions: IntGauge::new("active_sessions", "Game sessions held in memory")
+                .unwrap(),
+        };
+
+        // Names are fixed and unique, so registration can't fail
+        let registry = &metrics.registry;
+        registry.register(Box::new(metrics.games_started.clone())).unwrap();
+        registry.register(Box::new(metrics.games_won.clone())).unwrap();
+        registry.register(Box::new(metrics.games_lost.clone())).unwrap();
+        registry.register(Box::new(metrics.deposits.clone())).unwrap();
+        registry.register(Box::new(metrics.deposit_volume.clone())).unwrap();
+        registry.register(Box::new(metrics.active_sessions.clone())).unwrap();
+        metrics
+    }
+
+    // Render every collector in the Prometheus text exposition format
+    pub fn encode(&self) -> Result<String, prometheus::Error> {
+        TextEncoder::new().encode_to_string(&self.registry.gather())
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
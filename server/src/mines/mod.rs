@@ -1,11 +1,19 @@
 mod router;
+use bigdecimal::{BigDecimal, RoundingMode, ToPrimitive};
+use hmac::{Hmac, Mac};
 use rand::Rng;
+use sha2::{Digest, Sha256};
 use std::env;
+use crate::{
+    primitives::{Amount, ApiError, ApiErrorCode, WithErrorCode},
+    random_server,
+    server::GameConfig,
+};
 pub use router::router;
 use serde::{Deserialize, Serialize};
 use std::{
     collections::{HashMap, HashSet},
-    time::Duration,
+    str::FromStr,
 };
 use uuid::Uuid;
 
@@ -16,135 +24,195 @@ static RANDOM_SERVER_URL: Lazy<String> = Lazy::new(|| {
         .unwrap_or_else(|_| "http://localhost:3000".to_string())
 });
 
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct RandomNumberResponse {
-    success: bool,
-    #[serde(rename = "randomNumber")]
-    random_number: u32,
+// Generate a random 32-byte seed, hex encoded
+pub fn generate_seed() -> String {
+    let bytes: [u8; 32] = rand::thread_rng().r#gen();
+    hex::encode(bytes)
 }
 
-// Function to get random number for mines game - uses local random immediately
-// Makes fire-and-forget call to random server for logging/verification purposes only
-async fn get_mines_random_number(min: u32, max: u32) -> u32 {
-    // Use local random immediately for fast response
-    let mut rng = rand::thread_rng();
-    let local_random = rng.gen_range(min..=max);
-    
-    // Fire-and-forget call to random server (don't wait for response)
-    let server_url = RANDOM_SERVER_URL.clone();
-    tokio::spawn(async move {
-        // This runs in background, we don't care about the result
-        let _ = get_random_number_from_server_with_url(&server_url).await;
-    });
-    
-    local_random
+// SHA-256 commitment to a server seed, shown to the player before the game starts
+pub fn hash_seed(seed: &str) -> String {
+    hex::encode(Sha256::digest(seed.as_bytes()))
 }
 
-// Function to get random number from random-verifiable-server
-// Falls back to rand if server is unavailable
-async fn get_random_number_with_fallback(min: u32, max: u32) -> u32 {
-    match get_random_number_from_server().await {
-        Ok(num) => {
-            // Scale the 0-9 number to our desired range
-            let range = max - min + 1;
-            min + (num % range)
-        }
-        Err(_) => {
-            // Fallback to local rand if server is unavailable
-            let mut rng = rand::thread_rng();
-            rng.gen_range(min..=max)
+// Derive mine positions from the seeds: HMAC-SHA256 keyed by the server seed over
+// "client_seed:nonce:round" yields a byte stream that drives a partial Fisher-Yates
+// shuffle of 1..=blocks. Anyone holding the revealed seeds can recompute the board.
+pub fn derive_mine_positions(
+    server_seed: &str,
+    client_seed: &str,
+    nonce: u64,
+    blocks: u32,
+    mines: u32,
+) -> HashSet<u32> {
+    let mut tiles: Vec<u32> = (1..=blocks).collect();
+    let mut words = Vec::new();
+    let mut round = 0u64;
+
+    for i in 0..mines.min(blocks) as usize {
+        if words.is_empty() {
+            let mut mac = Hmac::<Sha256>::new_from_slice(server_seed.as_bytes())
+                .expect("HMAC accepts keys of any length");
+            mac.update(format!("{}:{}:{}", client_seed, nonce, round).as_bytes());
+            let digest = mac.finalize().into_bytes();
+            // Consume the digest as four big-endian u64 words, last word first via pop()
+            words = digest
+                .chunks(8)
+                .rev()
+                .map(|chunk| u64::from_be_bytes(chunk.try_into().unwrap()))
+                .collect();
+            round += 1;
         }
+        let word = words.pop().unwrap();
+        let j = i + (word % (tiles.len() - i) as u64) as usize;
+        tiles.swap(i, j);
     }
+
+    tiles.into_iter().take(mines as usize).collect()
 }
 
-// Internal function to call the random server with custom URL
-async fn get_random_number_from_server_with_url(server_url: &str) -> eyre::Result<u32> {
-    let client = reqwest::Client::new();
-    let response = client
-        .get(&format!("{}/random", server_url))
-        .send()
-        .await
-        .map_err(|e| eyre::eyre!("Failed to request random number: {}", e))?;
-    
-    if !response.status().is_success() {
-        return Err(eyre::eyre!("Random server returned error: {}", response.status()));
-    }
-    
-    let random_response: RandomNumberResponse = response
-        .json()
-        .await
-        .map_err(|e| eyre::eyre!("Failed to parse random number response: {}", e))?;
-    
-    if !random_response.success {
-        return Err(eyre::eyre!("Random server indicated failure"));
-    }
-    
-    Ok(random_response.random_number)
+// Draw mine positions from the random-verifiable-server, one uniform draw per mine over
+// the tiles still free. A verifiable game has no fallback: if the server can't be reached
+// the game isn't started, rather than quietly placing mines some other way
+async fn draw_verifiable_mine_positions(server_url: &str, blocks: u32, mines: u32) -> eyre::Result<HashSet<u32>> {
+    let mut free_tiles: Vec<u32> = (1..=blocks).collect();
+    let mut mine_positions = HashSet::with_capacity(mines as usize);
+
+    while mine_positions.len() < mines as usize && !free_tiles.is_empty() {
+        let index = random_server::get_random_below(server_url, free_tiles.len() as u64).await?;
+        mine_positions.insert(free_tiles.remove(index as usize));
+    }
+
+    Ok(mine_positions)
 }
 
-// Internal function to call the random server
-async fn get_random_number_from_server() -> eyre::Result<u32> {
-    get_random_number_from_server_with_url(&RANDOM_SERVER_URL).await
+// Fewest blocks a board can have: one mine and one safe tile
+pub const MIN_BLOCKS: u32 = 2;
+
+// Decimal places kept on multipliers; rounding is always down (in the house's favour)
+const MULTIPLIER_SCALE: i64 = 8;
+
+// Grid dimensions of a mines board, whose tiles are numbered 1..=rows*cols
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BoardSize {
+    pub rows: u32,
+    pub cols: u32,
 }
 
-const SESSION_TTL: Duration = Duration::from_secs(30 * 60);
+impl BoardSize {
+    // Square board with the given number of tiles, if there is one
+    pub fn square(blocks: u32) -> Option<Self> {
+        let side = blocks.isqrt();
+        (side * side == blocks).then_some(Self { rows: side, cols: side })
+    }
+
+    pub fn blocks(&self) -> Option<u32> {
+        self.rows.checked_mul(self.cols)
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StartGameRequest {
-    pub game_address: String,
-    pub amount: f64,
-    pub blocks: u32,
+    pub amount: Amount,
+    #[serde(default)]
+    pub blocks: u32, // rows * cols; may be omitted when rows and cols are given
+    pub rows: Option<u32>,
+    pub cols: Option<u32>,
     pub mines: u32,
+    pub client_seed: Option<String>, // Generated server-side when omitted
+    pub nonce: Option<u64>,
+    #[serde(default)]
+    pub verifiable: bool, // Draw mines from the random server instead of the seeds (slower)
+}
+
+impl StartGameRequest {
+    // Board requested either as explicit rows and cols, or as a square of `blocks` tiles
+    pub fn board(&self) -> eyre::Result<BoardSize> {
+        match (self.rows, self.cols) {
+            (Some(rows), Some(cols)) => {
+                let board = BoardSize { rows, cols };
+                if self.blocks != 0 && board.blocks() != Some(self.blocks) {
+                    return Err(eyre::eyre!("blocks must equal rows * cols"));
+                }
+                Ok(board)
+            }
+            (None, None) => BoardSize::square(self.blocks)
+                .ok_or_else(|| eyre::eyre!("rows and cols are required for non-square boards")),
+            _ => Err(eyre::eyre!("rows and cols must be given together")),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StartGameResponse {
     pub id: String,
-    pub amount: f64,
+    pub amount: Amount,
     pub blocks: u32,
+    pub rows: u32,
+    pub cols: u32,
     pub mines: u32,
+    pub server_seed_hash: String,
+    pub client_seed: String,
+    pub nonce: u64,
     pub session_status: SessionStatus,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MoveRequest {
-    pub game_address: String,
     pub id: String,
     pub block: u32,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchMoveRequest {
+    pub id: String,
+    pub blocks: Vec<u32>, // Revealed in order
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MoveAction {
     pub block: u32,
-    pub multiplier: f64,
+    pub multiplier: BigDecimal,
     pub safe: bool,
 }
 
+// One block of the finished board, so clients can render every tile
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TileState {
+    pub block: u32,
+    pub mine: bool,
+    pub revealed: bool, // Picked by the player during the game
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MoveResponse {
     pub id: String,
     pub actions: HashMap<String, MoveAction>,
-    pub current_multiplier: Option<f64>,
-    pub potential_payout: Option<f64>,
-    pub final_payout: Option<f64>,
+    pub current_multiplier: Option<BigDecimal>,
+    pub potential_payout: Option<BigDecimal>,
+    pub final_payout: Option<BigDecimal>, // Credited amount, clamped to the max payout
+    pub uncapped_payout: Option<BigDecimal>, // Bet times multiplier before the clamp
     pub bomb_blocks: Option<Vec<u32>>,
+    pub tile_map: Option<Vec<TileState>>, // Full board, once the game has ended
+    pub server_seed: Option<String>, // Revealed once the game has ended
     pub session_status: SessionStatus,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CashoutRequest {
-    pub game_address: String,
     pub id: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CashoutResponse {
     pub id: String,
-    pub src: f64,
-    pub final_payout: f64,
+    pub src: BigDecimal,
+    pub final_payout: BigDecimal,    // Credited amount, clamped to the max payout
+    pub uncapped_payout: BigDecimal, // Bet times multiplier before the clamp
     pub actions: HashMap<String, MoveAction>,
     pub bomb_blocks: Vec<u32>,
+    pub tile_map: Vec<TileState>,
+    pub server_seed: String,
     pub session_status: SessionStatus,
 }
 
@@ -152,14 +220,56 @@ pub struct CashoutResponse {
 pub struct GameSession {
     pub id: String,
     pub user_id: String,
-    pub src: f64,
+    pub src: BigDecimal,
     pub blocks: u32,
+    pub rows: u32,
+    pub cols: u32,
     pub mines: u32,
     pub mine_positions: HashSet<u32>,
     pub revealed_blocks: HashSet<u32>,
     pub actions: HashMap<String, MoveAction>,
-    pub current_multiplier: f64,
+    pub current_multiplier: BigDecimal,
+    pub server_seed: String, // Secret until the game ends
+    pub server_seed_hash: String,
+    pub client_seed: String,
+    pub nonce: u64,
     pub status: SessionStatus,
+    #[serde(default)]
+    pub version: u64, // Bumped on every save, see AppState::save_session
+    #[serde(default)]
+    pub bonus_wagered: BigDecimal, // Part of the bet counted towards a bonus wagering requirement
+}
+
+// Client-safe snapshot of a session: omits the mine positions and the unrevealed server seed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionView {
+    pub id: String,
+    pub src: BigDecimal,
+    pub blocks: u32,
+    pub rows: u32,
+    pub cols: u32,
+    pub mines: u32,
+    pub revealed_blocks: HashSet<u32>,
+    pub actions: HashMap<String, MoveAction>,
+    pub current_multiplier: BigDecimal,
+    pub potential_payout: BigDecimal,
+    pub server_seed_hash: String,
+    pub client_seed: String,
+    pub nonce: u64,
+    pub session_status: SessionStatus,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CancelRequest {
+    pub id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CancelResponse {
+    pub id: String,
+    pub refunded: BigDecimal, // The full original bet
+    pub server_seed: String,
+    pub session_status: SessionStatus,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -168,44 +278,114 @@ pub enum SessionStatus {
     Ended,
 }
 
+// Why a move or cashout was refused
+#[derive(Debug, thiserror::Error)]
+pub enum MoveError {
+    #[error("User ID does not match")]
+    WrongUser,
+    #[error("Session is not active")]
+    SessionNotActive,
+    #[error("Invalid block")]
+    InvalidBlock,
+    #[error("Cannot cancel a game once a tile has been revealed")]
+    TilesRevealed,
+}
+
+impl From<MoveError> for ApiError {
+    fn from(e: MoveError) -> Self {
+        let code = match e {
+            // Sessions are scoped to their owner, so to anyone else it doesn't exist
+            MoveError::WrongUser => ApiErrorCode::SessionNotFound,
+            MoveError::SessionNotActive => ApiErrorCode::SessionNotActive,
+            MoveError::InvalidBlock => ApiErrorCode::InvalidBlock,
+            MoveError::TilesRevealed => ApiErrorCode::TilesRevealed,
+        };
+        garden::api::bad_request(&e.to_string()).with_code(code)
+    }
+}
+
 impl GameSession {
-    pub async fn new(src: f64, blocks: u32, mines: u32, user_id: String) -> eyre::Result<Self> {
-        if blocks.isqrt() * blocks.isqrt() != blocks {
-            return Err(eyre::eyre!("Invalid Blocks"));
+    pub async fn new(
+        src: BigDecimal,
+        board: BoardSize,
+        mines: u32,
+        user_id: String,
+        client_seed: String,
+        nonce: u64,
+        verifiable: bool,
+        max_mines_fraction: f64,
+    ) -> eyre::Result<Self> {
+        if board.rows == 0 || board.cols == 0 {
+            return Err(eyre::eyre!("rows and cols must be positive"));
         }
-
-        let mut mine_positions = HashSet::with_capacity(mines as usize);
-        
-        // Generate mine positions using fast local random for mines game
-        while mine_positions.len() < mines as usize {
-            let position = get_mines_random_number(1, blocks).await;
-            mine_positions.insert(position);
+        let blocks = board.blocks().ok_or_else(|| eyre::eyre!("Board is too large"))?;
+        validate_mines(blocks, mines)?;
+        // Only new games are capped; verifying a finished game still accepts any valid board
+        let max_mines = (blocks as f64 * max_mines_fraction) as u32;
+        if mines > max_mines {
+            return Err(eyre::eyre!("mines must be at most {} on a {}-block board", max_mines, blocks));
         }
 
+        // Provably-fair placement: commit to a fresh server seed, reveal it when the game ends
+        let server_seed = generate_seed();
+        let server_seed_hash = hash_seed(&server_seed);
+        let mine_positions = if verifiable {
+            draw_verifiable_mine_positions(&RANDOM_SERVER_URL, blocks, mines).await?
+        } else {
+            derive_mine_positions(&server_seed, &client_seed, nonce, blocks, mines)
+        };
+
         Ok(GameSession {
             id: Uuid::new_v4().to_string(),
             src,
             blocks,
+            rows: board.rows,
+            cols: board.cols,
             user_id,
             mines,
             mine_positions,
             revealed_blocks: HashSet::new(),
             actions: HashMap::new(),
-            current_multiplier: 1.0,
+            current_multiplier: BigDecimal::from(1),
+            server_seed,
+            server_seed_hash,
+            client_seed,
+            nonce,
             status: SessionStatus::Active,
+            version: 0,
+            bonus_wagered: BigDecimal::from(0),
         })
     }
 
-    pub fn make_move(&mut self, block: u32, user_id: String) -> eyre::Result<MoveResponse> {
+    pub fn view(&self) -> SessionView {
+        SessionView {
+            id: self.id.clone(),
+            src: self.src.clone(),
+            blocks: self.blocks,
+            rows: self.rows,
+            cols: self.cols,
+            mines: self.mines,
+            revealed_blocks: self.revealed_blocks.clone(),
+            actions: self.actions.clone(),
+            current_multiplier: self.current_multiplier.clone(),
+            potential_payout: &self.src * &self.current_multiplier,
+            server_seed_hash: self.server_seed_hash.clone(),
+            client_seed: self.client_seed.clone(),
+            nonce: self.nonce,
+            session_status: self.status.clone(),
+        }
+    }
+
+    pub fn make_move(&mut self, block: u32, user_id: String, config: &GameConfig) -> Result<MoveResponse, MoveError> {
         if self.user_id != user_id {
-            return Err(eyre::eyre!("User ID does not match"));
+            return Err(MoveError::WrongUser);
         }
 
         if self.status != SessionStatus::Active {
-            return Err(eyre::eyre!("Session is not active"));
+            return Err(MoveError::SessionNotActive);
         }
         if block < 1 || block > self.blocks || self.revealed_blocks.contains(&block) {
-            return Err(eyre::eyre!("Invalid block"));
+            return Err(MoveError::InvalidBlock);
         }
 
         self.revealed_blocks.insert(block);
@@ -217,7 +397,7 @@ impl GameSession {
                 move_number,
                 MoveAction {
                     block,
-                    multiplier: 0.0,
+                    multiplier: BigDecimal::from(0),
                     safe: false,
                 },
             );
@@ -226,66 +406,422 @@ impl GameSession {
                 actions: self.actions.clone(),
                 current_multiplier: None,
                 potential_payout: None,
-                final_payout: Some(0.0),
+                final_payout: Some(BigDecimal::from(0)),
+                uncapped_payout: Some(BigDecimal::from(0)),
                 bomb_blocks: Some(self.mine_positions.iter().copied().collect()),
+                tile_map: Some(self.tile_map()),
+                server_seed: Some(self.server_seed.clone()),
                 session_status: SessionStatus::Ended,
             });
         }
 
         let safe_picks = self.revealed_blocks.len() as u32;
-        self.current_multiplier = self.calculate_multiplier(safe_picks);
+        self.current_multiplier = self.calculate_multiplier(safe_picks, config.house_edge);
         self.actions.insert(
             move_number,
             MoveAction {
                 block,
-                multiplier: self.current_multiplier,
+                multiplier: self.current_multiplier.clone(),
                 safe: true,
             },
         );
 
+        // Every safe tile is showing, so there is nothing left to risk: the game is won
+        // at the top multiplier. The caller clamps and credits the payout
+        if safe_picks == self.blocks - self.mines {
+            self.status = SessionStatus::Ended;
+            return Ok(MoveResponse {
+                id: self.id.clone(),
+                actions: self.actions.clone(),
+                current_multiplier: Some(self.current_multiplier.clone()),
+                potential_payout: None,
+                final_payout: Some(&self.src * &self.current_multiplier),
+                uncapped_payout: Some(&self.src * &self.current_multiplier),
+                bomb_blocks: Some(self.mine_positions.iter().copied().collect()),
+                tile_map: Some(self.tile_map()),
+                server_seed: Some(self.server_seed.clone()),
+                session_status: SessionStatus::Ended,
+            });
+        }
+
         Ok(MoveResponse {
             id: self.id.clone(),
             actions: self.actions.clone(),
-            current_multiplier: Some(self.current_multiplier),
-            potential_payout: Some(self.src * self.current_multiplier),
+            current_multiplier: Some(self.current_multiplier.clone()),
+            potential_payout: Some(&self.src * &self.current_multiplier),
             final_payout: None,
+            uncapped_payout: None,
             bomb_blocks: None,
+            tile_map: None,
+            server_seed: None,
             session_status: self.status.clone(),
         })
     }
 
-    pub fn cashout(&mut self, user_id: String) -> eyre::Result<CashoutResponse> {
+    // Reveal blocks in order, stopping at the first mine. The response is that of the
+    // last move made, so it carries every action so far and the final status
+    pub fn make_moves(&mut self, blocks: &[u32], user_id: String, config: &GameConfig) -> Result<MoveResponse, MoveError> {
+        let (last, rest) = blocks.split_last().ok_or(MoveError::InvalidBlock)?;
+        for &block in rest {
+            let response = self.make_move(block, user_id.clone(), config)?;
+            if response.session_status == SessionStatus::Ended {
+                return Ok(response);
+            }
+        }
+        self.make_move(*last, user_id, config)
+    }
+
+    pub fn cashout(&mut self, user_id: String, max_payout: &BigDecimal) -> Result<CashoutResponse, MoveError> {
         if self.user_id != user_id {
-            return Err(eyre::eyre!("User ID does not match"));
+            return Err(MoveError::WrongUser);
         }
 
         if self.status != SessionStatus::Active {
-            return Err(eyre::eyre!("Session is not active"));
+            return Err(MoveError::SessionNotActive);
         }
 
         self.status = SessionStatus::Ended;
-        let final_payout = self.src * self.current_multiplier;
+        let uncapped_payout = &self.src * &self.current_multiplier;
+        let final_payout = uncapped_payout.clone().min(max_payout.clone());
         Ok(CashoutResponse {
             id: self.id.clone(),
-            src: self.src,
+            src: self.src.clone(),
             final_payout,
+            uncapped_payout,
             actions: self.actions.clone(),
             bomb_blocks: self.mine_positions.iter().copied().collect(),
+            tile_map: self.tile_map(),
+            server_seed: self.server_seed.clone(),
             session_status: self.status.clone(),
         })
     }
 
-    fn calculate_multiplier(&self, safe_picks: u32) -> f64 {
-        const HOUSE_EDGE: f64 = 0.01; // 1% house edge
+    // Abandon a game before any tile is revealed. Nothing has been risked yet,
+    // so the whole bet is returned
+    pub fn cancel(&mut self, user_id: String) -> Result<CancelResponse, MoveError> {
+        if self.user_id != user_id {
+            return Err(MoveError::WrongUser);
+        }
+
+        if self.status != SessionStatus::Active {
+            return Err(MoveError::SessionNotActive);
+        }
+        if !self.revealed_blocks.is_empty() {
+            return Err(MoveError::TilesRevealed);
+        }
 
-        (0..safe_picks).fold(1.0, |acc, i| {
-            let remaining = self.blocks - self.mines - i;
+        self.status = SessionStatus::Ended;
+        Ok(CancelResponse {
+            id: self.id.clone(),
+            refunded: self.src.clone(),
+            server_seed: self.server_seed.clone(),
+            session_status: self.status.clone(),
+        })
+    }
+
+    // What an abandoned game hands back: the whole bet if no tile was revealed, as with
+    // cancel, and nothing once play has started
+    pub fn abandoned_refund(&self) -> Option<BigDecimal> {
+        (self.status == SessionStatus::Active && self.revealed_blocks.is_empty()).then(|| self.src.clone())
+    }
+
+    // Every block in order, marked mine or safe and whether the player picked it
+    pub fn tile_map(&self) -> Vec<TileState> {
+        (1..=self.blocks)
+            .map(|block| TileState {
+                block,
+                mine: self.mine_positions.contains(&block),
+                revealed: self.revealed_blocks.contains(&block),
+            })
+            .collect()
+    }
+
+    fn calculate_multiplier(&self, safe_picks: u32, house_edge: f64) -> BigDecimal {
+        calculate_multiplier(self.blocks, self.mines, safe_picks, house_edge)
+    }
+}
+
+fn validate_mines(blocks: u32, mines: u32) -> eyre::Result<()> {
+    if blocks == 0 {
+        return Err(eyre::eyre!("blocks must be positive"));
+    }
+    if mines == 0 {
+        return Err(eyre::eyre!("mines must be greater than zero"));
+    }
+    if mines >= blocks {
+        // Otherwise there would be no safe tile to reveal
+        return Err(eyre::eyre!("mines must be less than blocks"));
+    }
+    Ok(())
+}
+
+// Mine positions a finished game's revealed seeds produce, in ascending order
+pub fn verify_mine_positions(
+    server_seed: &str,
+    client_seed: &str,
+    nonce: u64,
+    blocks: u32,
+    mines: u32,
+) -> eyre::Result<Vec<u32>> {
+    validate_mines(blocks, mines)?;
+    let mut positions: Vec<u32> =
+        derive_mine_positions(server_seed, client_seed, nonce, blocks, mines).into_iter().collect();
+    positions.sort_unstable();
+    Ok(positions)
+}
+
+// Multiplier after each of 1..=(blocks - mines) safe picks, as a game would award it
+pub fn multiplier_table(blocks: u32, mines: u32, house_edge: f64) -> eyre::Result<Vec<BigDecimal>> {
+    validate_mines(blocks, mines)?;
+    Ok((1..=blocks - mines)
+        .map(|safe_picks| calculate_multiplier(blocks, mines, safe_picks, house_edge))
+        .collect())
+}
+
+// Expected return of cashing out after each of 1..=(blocks - mines) safe picks: the
+// chance of getting that far times the multiplier awarded for it
+pub fn rtp_table(blocks: u32, mines: u32, house_edge: f64) -> eyre::Result<Vec<f64>> {
+    let mut survival = 1.0;
+    Ok(multiplier_table(blocks, mines, house_edge)?
+        .iter()
+        .enumerate()
+        .map(|(picked, multiplier)| {
+            survival *= (blocks - mines - picked as u32) as f64 / (blocks - picked as u32) as f64;
+            survival * multiplier.to_f64().unwrap_or(0.0)
+        })
+        .collect())
+}
+
+fn calculate_multiplier(blocks: u32, mines: u32, safe_picks: u32, house_edge: f64) -> BigDecimal {
+    let house_edge = BigDecimal::from_str(&house_edge.to_string()).unwrap_or_default();
+    let edge_factor = BigDecimal::from(1) - house_edge;
+
+    // Accumulate numerator and denominator exactly, then divide once
+    let (numerator, denominator) = (0..safe_picks).fold(
+        (BigDecimal::from(1), BigDecimal::from(1)),
+        |(num, den), i| {
+            let remaining = blocks - mines - i;
             if remaining > 0 {
                 // Apply house edge: multiply by (1 - house_edge) to reduce payouts
-                acc * (1.0 - HOUSE_EDGE) * self.blocks as f64 / remaining as f64
+                (num * &edge_factor * BigDecimal::from(blocks), den * BigDecimal::from(remaining))
             } else {
-                acc
+                (num, den)
             }
-        })
+        },
+    );
+
+    (numerator / denominator).with_scale_round(MULTIPLIER_SCALE, RoundingMode::Down)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn new_test_session(src: BigDecimal, blocks: u32, mines: u32) -> GameSession {
+        let board = BoardSize::square(blocks).unwrap();
+        GameSession::new(src, board, mines, "user".to_string(), generate_seed(), 0, false, 1.0)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_three_pick_multiplier_is_exact() {
+        let session = new_test_session(BigDecimal::from(1), 25, 3).await;
+
+        // 0.99^3 * 25^3 / (22 * 21 * 20) = 15160.921875 / 9240, rounded down to 8 places
+        let expected = BigDecimal::from_str("1.64079241").unwrap();
+        assert_eq!(session.calculate_multiplier(3, 0.01), expected);
+    }
+
+    #[tokio::test]
+    async fn test_cashout_payout_is_bet_times_multiplier() {
+        let bet = BigDecimal::from_str("0.1").unwrap();
+        let mut session = new_test_session(bet.clone(), 25, 3).await;
+        let safe_block = (1..=25).find(|b| !session.mine_positions.contains(b)).unwrap();
+        session.make_move(safe_block, "user".to_string(), &GameConfig::default()).unwrap();
+
+        let response = session.cashout("user".to_string(), &BigDecimal::from(1000)).unwrap();
+        assert_eq!(response.final_payout, bet * session.calculate_multiplier(1, 0.01));
+    }
+
+    #[test]
+    fn test_same_seeds_reproduce_mine_positions() {
+        let first = derive_mine_positions("server-seed", "client-seed", 7, 25, 5);
+        let second = derive_mine_positions("server-seed", "client-seed", 7, 25, 5);
+        assert_eq!(first, second);
+        assert_eq!(first.len(), 5);
+        assert!(first.iter().all(|p| (1..=25).contains(p)));
+
+        let other_nonce = derive_mine_positions("server-seed", "client-seed", 8, 25, 5);
+        assert_ne!(first, other_nonce);
+    }
+
+    #[tokio::test]
+    async fn test_revealed_seed_recomputes_board() {
+        let mut session = new_test_session(BigDecimal::from(1), 25, 3).await;
+        assert_eq!(hash_seed(&session.server_seed), session.server_seed_hash);
+
+        let response = session.cashout("user".to_string(), &BigDecimal::from(1000)).unwrap();
+        let recomputed =
+            derive_mine_positions(&response.server_seed, &session.client_seed, session.nonce, 25, 3);
+        assert_eq!(recomputed, session.mine_positions);
+    }
+
+    // Random server answering the given digits in turn, then 0 forever
+    async fn spawn_digit_server(digits: Vec<u32>) -> String {
+        use axum::{Json, Router, routing::get};
+        use std::sync::{Arc, Mutex};
+
+        let digits = Arc::new(Mutex::new(digits.into_iter()));
+        let app = Router::new().route(
+            "/random",
+            get(move || {
+                let digit = digits.lock().unwrap().next().unwrap_or(0);
+                async move { Json(serde_json::json!({ "success": true, "randomNumber": digit })) }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_verifiable_positions_come_from_random_server() {
+        // Digits 9,9 pick the last of 25 free tiles, then 2,3 the last of the 24 left
+        let url = spawn_digit_server(vec![9, 9, 2, 3]).await;
+        let positions = draw_verifiable_mine_positions(&url, 25, 3).await.unwrap();
+        assert_eq!(positions, HashSet::from([25, 24, 1]));
+
+        // No local fallback: an unreachable server means no board
+        assert!(draw_verifiable_mine_positions("http://127.0.0.1:1", 25, 3).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rejects_invalid_mine_counts() {
+        for (mines, expected) in [
+            (0, "mines must be greater than zero"),
+            (25, "mines must be less than blocks"),
+            (30, "mines must be less than blocks"),
+        ] {
+            let err = GameSession::new(
+                BigDecimal::from(1),
+                BoardSize::square(25).unwrap(),
+                mines,
+                "user".to_string(),
+                generate_seed(),
+                0,
+                false,
+                1.0,
+            )
+            .await
+            .unwrap_err();
+            assert_eq!(err.to_string(), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rejects_boards_denser_than_the_configured_fraction() {
+        let start = |mines| {
+            GameSession::new(
+                BigDecimal::from(1),
+                BoardSize::square(25).unwrap(),
+                mines,
+                "user".to_string(),
+                generate_seed(),
+                0,
+                false,
+                0.8,
+            )
+        };
+
+        let err = start(21).await.unwrap_err();
+        assert_eq!(err.to_string(), "mines must be at most 20 on a 25-block board");
+
+        let session = start(20).await.unwrap();
+        assert_eq!(session.mine_positions.len(), 20);
+    }
+
+    #[tokio::test]
+    async fn test_densest_board_terminates() {
+        let session = new_test_session(BigDecimal::from(1), 25, 24).await;
+        assert_eq!(session.mine_positions.len(), 24);
+    }
+
+    #[tokio::test]
+    async fn test_cashout_clamps_to_max_payout() {
+        // One safe tile short of clearing the board, which would end the game itself
+        let mut session = new_test_session(BigDecimal::from(10), 25, 23).await;
+        let safe_block = (1..=25).find(|b| !session.mine_positions.contains(b)).unwrap();
+        session.make_move(safe_block, "user".to_string(), &GameConfig::default()).unwrap();
+
+        let cap = BigDecimal::from(50);
+        let response = session.cashout("user".to_string(), &cap).unwrap();
+        assert!(response.uncapped_payout > cap);
+        assert_eq!(response.final_payout, cap);
+    }
+
+    #[tokio::test]
+    async fn test_tile_map_covers_board_once_game_ends() {
+        let config = GameConfig::default();
+        let mut session = new_test_session(BigDecimal::from(1), 25, 3).await;
+        let safe_block = (1..=25).find(|b| !session.mine_positions.contains(b)).unwrap();
+        let mine_block = *session.mine_positions.iter().next().unwrap();
+
+        let response = session.make_move(safe_block, "user".to_string(), &config).unwrap();
+        assert!(response.tile_map.is_none());
+
+        let response = session.make_move(mine_block, "user".to_string(), &config).unwrap();
+        let tile_map = response.tile_map.unwrap();
+        assert_eq!(tile_map.len(), 25);
+        assert_eq!(tile_map.iter().filter(|t| t.mine).count(), 3);
+        assert!(tile_map.iter().all(|t| t.revealed == (t.block == safe_block || t.block == mine_block)));
+
+        let mut session = new_test_session(BigDecimal::from(1), 16, 5).await;
+        let response = session.cashout("user".to_string(), &BigDecimal::from(1000)).unwrap();
+        assert_eq!(response.tile_map.len(), 16);
+        assert_eq!(response.tile_map.iter().filter(|t| t.mine).count(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_rectangular_boards() {
+        for (rows, cols, mines) in [(5, 4, 3), (1, 10, 9)] {
+            let session = GameSession::new(
+                BigDecimal::from(1),
+                BoardSize { rows, cols },
+                mines,
+                "user".to_string(),
+                generate_seed(),
+                0,
+                false,
+                1.0,
+            )
+            .await
+            .unwrap();
+            assert_eq!(session.blocks, rows * cols);
+            assert_eq!(session.mine_positions.len(), mines as usize);
+            assert!(session.mine_positions.iter().all(|p| (1..=rows * cols).contains(p)));
+            assert_eq!(session.tile_map().len(), (rows * cols) as usize);
+        }
+    }
+
+    #[test]
+    fn test_request_board_dimensions() {
+        let request = |blocks: u32, rows: Option<u32>, cols: Option<u32>| StartGameRequest {
+            amount: "1".parse().unwrap(),
+            blocks,
+            rows,
+            cols,
+            mines: 1,
+            client_seed: None,
+            nonce: None,
+            verifiable: false,
+        };
+        assert_eq!(request(0, Some(5), Some(4)).board().unwrap(), BoardSize { rows: 5, cols: 4 });
+        assert_eq!(request(20, Some(5), Some(4)).board().unwrap(), BoardSize { rows: 5, cols: 4 });
+        assert_eq!(request(25, None, None).board().unwrap(), BoardSize { rows: 5, cols: 5 });
+        assert!(request(21, Some(5), Some(4)).board().is_err());
+        assert!(request(20, None, None).board().is_err());
+        assert!(request(0, Some(5), None).board().is_err());
     }
 }
@@ -1,47 +1,62 @@
 use crate::{
     mines::{
-        CashoutRequest, CashoutResponse, GameSession, MoveRequest, MoveResponse, SESSION_TTL,
-        SessionStatus, StartGameRequest, StartGameResponse,
+        CashoutRequest, CashoutResponse, GameSession, MoveRequest, MoveResponse, SessionStatus, StartGameRequest, StartGameResponse, generate_seed,
     },
-    primitives::new_moka_cache,
+    primitives::{ApiErrorCode, CodedResult, WithErrorCode, bet_request},
     server::{AppState, Service},
     store::GameTransaction,
 };
 use axum::{
-    Json, Router,
-    extract::State,
+    Extension, Json, Router,
+    extract::{State, rejection::JsonRejection},
     routing::post,
 };
 use garden::api::{
     bad_request, internal_error,
-    primitives::{ApiResult, Response},
+    primitives::Response,
 };
 use serde_json::to_value;
 use sqlx::types::BigDecimal;
-use std::{sync::Arc, str::FromStr};
+use std::sync::Arc;
 
 async fn start_game(
     State(state): State<Arc<AppState>>,
-    Json(payload): Json<StartGameRequest>,
-) -> ApiResult<StartGameResponse> {
-    // Get user from database using game_address
-    let user = state.store.get_user_by_evm_addr(&payload.game_address).await
+    Extension(user_id): Extension<String>,
+    payload: Result<Json<serde_json::Value>, JsonRejection>,
+) -> CodedResult<StartGameResponse> {
+    state.ensure_betting_open()?;
+    let payload: StartGameRequest = bet_request(payload)?;
+
+    // The auth middleware hands over the token's subject, a user id
+    let user = state.store.get_user_by_id(&user_id).await
         .map_err(|e| internal_error(&format!("Database error: {}", e)))?
-        .ok_or_else(|| bad_request("User not found for game address"))?;
-
-    // Check if user has enough in-game balance
-    let bet_amount = BigDecimal::from_str(&payload.amount.to_string())
-        .map_err(|_| bad_request("Invalid amount format"))?;
-    if user.in_game_balance < bet_amount {
-        return Err(bad_request("Insufficient in-game balance"));
-    }
-
-    // Deduct bet amount from user's in-game balance
-    let _updated_user = state.store.adjust_in_game_balance(&user.user_id, &(-bet_amount.clone())).await
-        .map_err(|e| internal_error(&format!("Failed to deduct in-game balance: {}", e)))?;
-
-    let session = GameSession::new(payload.amount, payload.blocks, payload.mines, user.user_id.clone()).await
-        .map_err(|e| bad_request(&e.to_string()))?;
+        .filter(|user| user.deleted_at.is_none())
+        .ok_or_else(|| bad_request("User not found").with_code(ApiErrorCode::UserNotFound))?;
+
+    let bet_amount = payload.amount.as_decimal().clone();
+    state.bet_limits.validate(&bet_amount)
+        .map_err(|e| bad_request(e).with_code(ApiErrorCode::InvalidAmount))?;
+
+    let board = payload.board().map_err(|e| bad_request(&e.to_string()))?;
+    let client_seed = payload.client_seed.clone().unwrap_or_else(generate_seed);
+    let session = GameSession::new(
+        bet_amount.clone(),
+        board,
+        payload.mines,
+        user.user_id.clone(),
+        client_seed,
+        payload.nonce.unwrap_or(0),
+        payload.verifiable,
+        state.game_config.max_mines_fraction,
+    )
+    .await
+    .map_err(|e| bad_request(&e.to_string()))?;
+
+    // Validate the board before taking the bet, then deduct atomically so
+    // concurrent bets can't overdraw the balance
+    let _updated_user = state.store.try_deduct_in_game_balance(&user.user_id, &bet_amount).await
+        .map_err(|e| internal_error(&format!("Failed to deduct in-game balance: {}", e)))?
+        .ok_or_else(|| bad_request("Insufficient in-game balance").with_code(ApiErrorCode::InsufficientBalance))?;
 
     // Record game start transaction
     let transaction = GameTransaction {
@@ -61,64 +76,67 @@ async fn start_game(
     let response = StartGameResponse {
         id: session.id.clone(),
         amount: payload.amount,
-        blocks: payload.blocks,
+        blocks: session.blocks,
+        rows: session.rows,
+        cols: session.cols,
         mines: payload.mines,
+        server_seed_hash: session.server_seed_hash.clone(),
+        client_seed: session.client_seed.clone(),
+        nonce: session.nonce,
         session_status: SessionStatus::Active,
     };
 
-    let service_state = match state.sessions.get(&Service::Mines).await {
-        Some(cache) => cache,
-        None => {
-            let cache = new_moka_cache(SESSION_TTL);
-            state.sessions.insert(Service::Mines, cache.clone()).await;
-            cache
-        }
-    };
-
-    service_state
-        .insert(
-            session.id.clone(),
+    state
+        .save_session(
+            &Service::Mines,
+            &user.user_id,
+            &session.id,
             to_value(&session).map_err(|_| internal_error("Serialization error"))?,
         )
-        .await;
+        .await
+        .map_err(|e| internal_error(&format!("Failed to save session: {}", e)))?;
 
     Ok(Response::ok(response))
 }
 
 async fn make_move(
     State(state): State<Arc<AppState>>,
+    Extension(user_id): Extension<String>,
     Json(payload): Json<MoveRequest>,
-) -> ApiResult<MoveResponse> {
-    // Get user from database using game_address
-    let user = state.store.get_user_by_evm_addr(&payload.game_address).await
+) -> CodedResult<MoveResponse> {
+    // The auth middleware hands over the token's subject, a user id
+    let user = state.store.get_user_by_id(&user_id).await
         .map_err(|e| internal_error(&format!("Database error: {}", e)))?
-        .ok_or_else(|| bad_request("User not found for game address"))?;
+        .filter(|user| user.deleted_at.is_none())
+        .ok_or_else(|| bad_request("User not found").with_code(ApiErrorCode::UserNotFound))?;
 
-    let service_state = state
-        .sessions
-        .get(&Service::Mines)
-        .await
-        .ok_or(bad_request("Session not found"))?;
-    let mut session: GameSession = service_state
-        .get(&payload.id)
+    let mut session: GameSession = state
+        .load_session(&Service::Mines, &user.user_id, &payload.id)
         .await
-        .and_then(|v| serde_json::from_value(v.clone()).ok())
-        .ok_or(bad_request("Session not found"))?;
+        .map_err(|e| internal_error(&format!("Database error: {}", e)))?
+        .and_then(|v| serde_json::from_value(v).ok())
+        .ok_or_else(|| bad_request("Session not found").with_code(ApiErrorCode::SessionNotFound))?;
 
     let response = session
-        .make_move(payload.block, user.user_id.clone())
-        .map_err(|e| bad_request(&e.to_string()))?;
-    service_state
-        .insert(
-            session.id.clone(),
-            to_value(&session).map_err(|_| internal_error("Serialization error"))?,
-        )
-        .await;
+        .make_move(payload.block, user.user_id.clone(), &state.game_config)?;
 
     if response.session_status == SessionStatus::Ended {
         // If the game ended (hit a mine), no additional balance changes needed
         // as the bet was already deducted when the game started
-        service_state.remove(&payload.id).await;
+        state
+            .remove_session(&Service::Mines, &user.user_id, &payload.id)
+            .await
+            .map_err(|e| internal_error(&format!("Failed to remove session: {}", e)))?;
+    } else {
+        state
+            .save_session(
+                &Service::Mines,
+                &user.user_id,
+                &session.id,
+                to_value(&session).map_err(|_| internal_error("Serialization error"))?,
+            )
+            .await
+            .map_err(|e| internal_error(&format!("Failed to save session: {}", e)))?;
     }
 
     Ok(Response::ok(response))
@@ -126,31 +144,27 @@ async fn make_move(
 
 async fn cashout(
     State(state): State<Arc<AppState>>,
+    Extension(user_id): Extension<String>,
     Json(payload): Json<CashoutRequest>,
-) -> ApiResult<CashoutResponse> {
-    // Get user from database using game_address
-    let user = state.store.get_user_by_evm_addr(&payload.game_address).await
+) -> CodedResult<CashoutResponse> {
+    // The auth middleware hands over the token's subject, a user id
+    let user = state.store.get_user_by_id(&user_id).await
         .map_err(|e| internal_error(&format!("Database error: {}", e)))?
-        .ok_or_else(|| bad_request("User not found for game address"))?;
+        .filter(|user| user.deleted_at.is_none())
+        .ok_or_else(|| bad_request("User not found").with_code(ApiErrorCode::UserNotFound))?;
 
-    let service_state = state
-        .sessions
-        .get(&Service::Mines)
-        .await
-        .ok_or(bad_request("Session not found"))?;
-    let mut session: GameSession = service_state
-        .get(&payload.id)
+    let mut session: GameSession = state
+        .load_session(&Service::Mines, &user.user_id, &payload.id)
         .await
-        .and_then(|v| serde_json::from_value(v.clone()).ok())
-        .ok_or(bad_request("Session not found"))?;
+        .map_err(|e| internal_error(&format!("Database error: {}", e)))?
+        .and_then(|v| serde_json::from_value(v).ok())
+        .ok_or_else(|| bad_request("Session not found").with_code(ApiErrorCode::SessionNotFound))?;
 
     let response = session
-        .cashout(user.user_id.clone())
-        .map_err(|e| bad_request(&e.to_string()))?;
+        .cashout(user.user_id.clone(), &state.max_payout)?;
 
     // Add winnings to user's balance
-    let payout_amount = BigDecimal::from_str(&response.final_payout.to_string())
-        .map_err(|_| internal_error("Invalid payout amount"))?;
+    let payout_amount = response.final_payout.clone();
     if payout_amount > BigDecimal::from(0) {
         let _updated_user = state.store.adjust_in_game_balance(&user.user_id, &payout_amount).await
             .map_err(|e| internal_error(&format!("Failed to add winnings: {}", e)))?;
@@ -171,12 +185,10 @@ async fn cashout(
             .map_err(|e| internal_error(&format!("Failed to record win transaction: {}", e)))?;
     }
 
-    service_state
-        .insert(
-            session.id.clone(),
-            to_value(&session).map_err(|_| internal_error("Serialization error"))?,
-        )
-        .await;
+    state
+        .remove_session(&Service::Mines, &user.user_id, &session.id)
+        .await
+        .map_err(|e| internal_error(&format!("Failed to remove session: {}", e)))?;
 
     Ok(Response::ok(response))
 }
@@ -1,8 +1,297 @@
+use axum::{Json, extract::rejection::JsonRejection, http::StatusCode, response::IntoResponse};
+use bigdecimal::ToPrimitive;
+use garden::api::primitives::Response;
 use moka::future::Cache;
-use std::{hash::Hash, sync::Arc, time::Duration};
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de, de::DeserializeOwned};
+use sqlx::types::BigDecimal;
+use std::{fmt, hash::Hash, str::FromStr, sync::Arc, time::Duration};
 
 pub fn new_moka_cache<T: Eq + Hash + Send + Sync + 'static, U: Clone + Send + Sync + 'static>(
     ttl: Duration,
 ) -> Arc<Cache<T, U>> {
     Arc::new(Cache::builder().time_to_live(ttl).build())
 }
+
+// Result type for handlers that need error statuses beyond the garden helpers
+pub type HttpResult<T> = Result<Response<T>, axum::response::Response>;
+
+// Re-status an API error response (e.g. a garden `bad_request`) with the given status code
+pub fn with_status<E: IntoResponse>(status: StatusCode, err: E) -> axum::response::Response {
+    (status, err).into_response()
+}
+
+// Machine-readable reason for an error, sent next to the message so clients don't have
+// to match on prose
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ApiErrorCode {
+    InvalidAmount,
+    InsufficientBalance,
+    UserNotFound,
+    SessionNotFound,
+    SessionNotActive,
+    InvalidBlock,
+    TilesRevealed,
+    WithdrawalLimitExceeded,
+    TooManyActiveGames,
+    BonusWageringIncomplete,
+    BettingDisabled,
+}
+
+// A garden error response, optionally tagged with an ApiErrorCode. Untagged errors
+// convert with `?` and render exactly as before
+pub struct ApiError {
+    response: Response<()>,
+    code: Option<ApiErrorCode>,
+    status: Option<StatusCode>, // Overrides the status of the garden response
+}
+
+pub type CodedResult<T> = Result<Response<T>, ApiError>;
+
+impl From<Response<()>> for ApiError {
+    fn from(response: Response<()>) -> Self {
+        Self { response, code: None, status: None }
+    }
+}
+
+impl ApiError {
+    // Send with a status the garden helpers have no constructor for
+    pub fn with_status_code(mut self, status: StatusCode) -> Self {
+        self.status = Some(status);
+        self
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> axum::response::Response {
+        let response = match self.code {
+            Some(code) => {
+                let mut body = serde_json::to_value(&self.response).unwrap_or_default();
+                body["code"] = serde_json::json!(code);
+                let status = self.response.into_response().status();
+                (status, Json(body)).into_response()
+            }
+            None => self.response.into_response(),
+        };
+        match self.status {
+            Some(status) => with_status(status, response),
+            None => response,
+        }
+    }
+}
+
+pub trait WithErrorCode {
+    fn with_code(self, code: ApiErrorCode) -> ApiError;
+}
+
+impl WithErrorCode for Response<()> {
+    fn with_code(self, code: ApiErrorCode) -> ApiError {
+        ApiError { response: self, code: Some(code), status: None }
+    }
+}
+
+// Unwrap the body of a game start. Axum answers a body that doesn't parse with a bare
+// 422; a bet answers 400 like its other validation, before any balance is touched. The
+// amount is checked on its own first, so exactly the bets whose amount is at fault
+// (missing, negative, NaN, infinite or not a number) are tagged InvalidAmount
+pub fn bet_request<T: DeserializeOwned>(payload: Result<Json<serde_json::Value>, JsonRejection>) -> Result<T, ApiError> {
+    let Json(body) = payload.map_err(|rejection| ApiError::from(garden::api::bad_request(&rejection.body_text())))?;
+    if let Some(fields) = body.as_object() {
+        fields
+            .get("amount")
+            .ok_or(AmountError::Missing)
+            .and_then(Amount::try_from)
+            .map_err(|e| garden::api::bad_request(&e.to_string()).with_code(ApiErrorCode::InvalidAmount))?;
+    }
+    serde_json::from_value(body).map_err(|e| garden::api::bad_request(&e.to_string()).into())
+}
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum AmountError {
+    #[error("invalid amount: {0}")]
+    Invalid(String),
+    #[error("amount must not be negative")]
+    Negative,
+    #[error("amount must be finite")]
+    NotFinite,
+    #[error("amount is required")]
+    Missing,
+}
+
+// A non-negative monetary amount. Requests may send it as a JSON number or a decimal
+// string; it is always written back as a decimal string, so no digits are lost to a float
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Amount(BigDecimal);
+
+impl Amount {
+    pub fn as_decimal(&self) -> &BigDecimal {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> BigDecimal {
+        self.0
+    }
+
+    // For the game math, which works in floats
+    pub fn to_f64(&self) -> f64 {
+        self.0.to_f64().unwrap_or_default()
+    }
+}
+
+impl TryFrom<BigDecimal> for Amount {
+    type Error = AmountError;
+
+    fn try_from(value: BigDecimal) -> Result<Self, Self::Error> {
+        if value < BigDecimal::from(0) {
+            return Err(AmountError::Negative);
+        }
+        Ok(Self(value))
+    }
+}
+
+impl TryFrom<f64> for Amount {
+    type Error = AmountError;
+
+    // Goes through the shortest decimal form of the float, so 0.1 stays 0.1
+    fn try_from(value: f64) -> Result<Self, Self::Error> {
+        if !value.is_finite() {
+            return Err(AmountError::NotFinite);
+        }
+        value.to_string().parse()
+    }
+}
+
+impl TryFrom<&serde_json::Value> for Amount {
+    type Error = AmountError;
+
+    // A JSON number or decimal string, read the same way as when deserializing
+    fn try_from(value: &serde_json::Value) -> Result<Self, Self::Error> {
+        match value {
+            serde_json::Value::Number(number) => match (number.as_u64(), number.as_i64(), number.as_f64()) {
+                (Some(unsigned), _, _) => Ok(Self(BigDecimal::from(unsigned))),
+                (_, Some(signed), _) => Self::try_from(BigDecimal::from(signed)),
+                (_, _, Some(float)) => Self::try_from(float),
+                _ => Err(AmountError::Invalid(number.to_string())),
+            },
+            serde_json::Value::String(text) => text.parse(),
+            other => Err(AmountError::Invalid(other.to_string())),
+        }
+    }
+}
+
+impl FromStr for Amount {
+    type Err = AmountError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let value = BigDecimal::from_str(s.trim()).map_err(|_| AmountError::Invalid(s.to_string()))?;
+        Self::try_from(value)
+    }
+}
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl From<Amount> for BigDecimal {
+    fn from(amount: Amount) -> Self {
+        amount.0
+    }
+}
+
+impl Serialize for Amount {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Amount {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct AmountVisitor;
+
+        impl de::Visitor<'_> for AmountVisitor {
+            type Value = Amount;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a non-negative amount, as a number or a decimal string")
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Amount, E> {
+                Ok(Amount(BigDecimal::from(v)))
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Amount, E> {
+                Amount::try_from(BigDecimal::from(v)).map_err(E::custom)
+            }
+
+            fn visit_f64<E: de::Error>(self, v: f64) -> Result<Amount, E> {
+                Amount::try_from(v).map_err(E::custom)
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Amount, E> {
+                v.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(AmountVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_amount_parses_decimal_strings_and_floats() {
+        let amount: Amount = "1.25".parse().unwrap();
+        assert_eq!(amount.as_decimal(), &BigDecimal::from_str("1.25").unwrap());
+        assert_eq!(amount.to_string(), "1.25");
+        assert_eq!(Amount::try_from(0.1).unwrap(), "0.1".parse().unwrap());
+        assert_eq!(" 0 ".parse::<Amount>().unwrap(), Amount::try_from(0.0).unwrap());
+        assert!(matches!("ten".parse::<Amount>(), Err(AmountError::Invalid(_))));
+
+        // Accepted as a number or a string, written back as a string
+        let from_number: Amount = serde_json::from_str("2.5").unwrap();
+        let from_string: Amount = serde_json::from_str("\"2.5\"").unwrap();
+        assert_eq!(from_number, from_string);
+        assert_eq!(serde_json::from_str::<Amount>("3").unwrap(), "3".parse().unwrap());
+        assert_eq!(serde_json::to_string(&from_number).unwrap(), "\"2.5\"");
+
+        // More digits than an f64 holds survive the round trip
+        let precise: Amount = "12345678901234567890.123456789".parse().unwrap();
+        let written = serde_json::to_string(&precise).unwrap();
+        assert_eq!(written, "\"12345678901234567890.123456789\"");
+        assert_eq!(serde_json::from_str::<Amount>(&written).unwrap(), precise);
+    }
+
+    #[test]
+    fn test_amount_rejects_negative_values() {
+        assert_eq!("-1".parse::<Amount>(), Err(AmountError::Negative));
+        assert_eq!(Amount::try_from(-0.5), Err(AmountError::Negative));
+        assert!(serde_json::from_str::<Amount>("-2").is_err());
+        assert!(serde_json::from_str::<Amount>("-2.5").is_err());
+        assert!(serde_json::from_str::<Amount>("\"-2.5\"").is_err());
+    }
+
+    #[test]
+    fn test_amount_reads_json_values_like_deserializing() {
+        for value in [serde_json::json!(2.5), serde_json::json!("2.5"), serde_json::json!(3), serde_json::json!(-1)] {
+            let read = Amount::try_from(&value);
+            let deserialized = serde_json::from_value::<Amount>(value.clone()).map_err(|_| ());
+            assert_eq!(read.is_ok(), deserialized.is_ok(), "{}", value);
+            if let (Ok(read), Ok(deserialized)) = (read, deserialized) {
+                assert_eq!(read, deserialized);
+            }
+        }
+        assert_eq!(Amount::try_from(&serde_json::json!(-1)), Err(AmountError::Negative));
+        assert!(matches!(Amount::try_from(&serde_json::json!(true)), Err(AmountError::Invalid(_))));
+    }
+
+    #[test]
+    fn test_amount_rejects_nan_and_infinity() {
+        assert_eq!(Amount::try_from(f64::NAN), Err(AmountError::NotFinite));
+        assert_eq!(Amount::try_from(f64::INFINITY), Err(AmountError::NotFinite));
+        assert_eq!(Amount::try_from(f64::NEG_INFINITY), Err(AmountError::NotFinite));
+    }
+}
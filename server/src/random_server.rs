@@ -0,0 +1,212 @@
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::{env, future::Future, time::Duration};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RandomNumberResponse {
+    success: bool,
+    #[serde(rename = "randomNumber")]
+    random_number: u32,
+}
+
+// How hard to try the random server before the caller falls back or gives up
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub attempts: u32,
+    pub initial_backoff: Duration, // Doubled after every failed attempt
+}
+
+impl RetryPolicy {
+    // Read RANDOM_SERVER_ATTEMPTS / RANDOM_SERVER_BACKOFF_MS from the environment, with defaults
+    fn from_env() -> Self {
+        let read = |key: &str, default: u64| {
+            env::var(key)
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default)
+        };
+        Self {
+            attempts: read("RANDOM_SERVER_ATTEMPTS", 3).max(1) as u32,
+            initial_backoff: Duration::from_millis(read("RANDOM_SERVER_BACKOFF_MS", 100)),
+        }
+    }
+}
+
+static RETRY_POLICY: Lazy<RetryPolicy> = Lazy::new(RetryPolicy::from_env);
+
+// The random server answers single digits, 0 through 9
+const DIGIT_BASE: u64 = 10;
+
+// Draws a uniform value may throw away before giving up; each is less likely than not
+const MAX_REDRAWS: u32 = 32;
+
+// Ask the random server for a number, retrying transient failures with the configured backoff
+pub async fn get_random_number(server_url: &str) -> eyre::Result<u32> {
+    get_random_number_with_retry(server_url, &RETRY_POLICY).await
+}
+
+// A uniform draw from 0..bound, built from as many server digits as the bound needs
+pub async fn get_random_below(server_url: &str, bound: u64) -> eyre::Result<u64> {
+    uniform_below(bound, || get_random_number(server_url)).await
+}
+
+// Reads enough digits to cover the bound as one base-10 number. Numbers at or past the
+// largest multiple of the bound are redrawn, so reducing the rest favours no value
+async fn uniform_below<F, Fut>(bound: u64, mut next_digit: F) -> eyre::Result<u64>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = eyre::Result<u32>>,
+{
+    if bound == 0 {
+        return Err(eyre::eyre!("Cannot draw from an empty range"));
+    }
+    let mut digits = 1;
+    let mut span = DIGIT_BASE;
+    while span < bound {
+        span = span
+            .checked_mul(DIGIT_BASE)
+            .ok_or_else(|| eyre::eyre!("Cannot draw from a range of {}", bound))?;
+        digits += 1;
+    }
+    let limit = span - span % bound;
+
+    for _ in 0..=MAX_REDRAWS {
+        let mut value = 0;
+        for _ in 0..digits {
+            let digit = next_digit().await? as u64;
+            if digit >= DIGIT_BASE {
+                return Err(eyre::eyre!("Random server returned {}, not a single digit", digit));
+            }
+            value = value * DIGIT_BASE + digit;
+        }
+        if value < limit {
+            return Ok(value % bound);
+        }
+    }
+    Err(eyre::eyre!("Random server draws kept falling outside 0..{}", bound))
+}
+
+// Returns the last error once every attempt has failed
+pub async fn get_random_number_with_retry(server_url: &str, policy: &RetryPolicy) -> eyre::Result<u32> {
+    let mut backoff = policy.initial_backoff;
+    let mut attempt = 1;
+    loop {
+        match request_random_number(server_url).await {
+            Ok(number) => return Ok(number),
+            Err(e) if attempt >= policy.attempts => return Err(e),
+            Err(e) => {
+                tracing::debug!("Random server attempt {} of {} failed: {}", attempt, policy.attempts, e);
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+async fn request_random_number(server_url: &str) -> eyre::Result<u32> {
+    let client = reqwest::Client::new();
+    let response = client
+        .get(format!("{}/random", server_url))
+        .send()
+        .await
+        .map_err(|e| eyre::eyre!("Failed to request random number: {}", e))?;
+
+    if !response.status().is_success() {
+        return Err(eyre::eyre!("Random server returned error: {}", response.status()));
+    }
+
+    let random_response: RandomNumberResponse = response
+        .json()
+        .await
+        .map_err(|e| eyre::eyre!("Failed to parse random number response: {}", e))?;
+
+    if !random_response.success {
+        return Err(eyre::eyre!("Random server indicated failure"));
+    }
+
+    Ok(random_response.random_number)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Json, Router, http::StatusCode, response::IntoResponse, routing::get};
+    use rand::Rng;
+    use std::sync::{
+        Arc,
+        atomic::{AtomicU32, Ordering},
+    };
+
+    // Random server failing its first `failures` requests, then answering 7
+    async fn spawn_flaky_server(failures: u32) -> (String, Arc<AtomicU32>) {
+        let requests = Arc::new(AtomicU32::new(0));
+        let seen = requests.clone();
+        let app = Router::new().route(
+            "/random",
+            get(move || {
+                let seen = seen.clone();
+                async move {
+                    if seen.fetch_add(1, Ordering::SeqCst) < failures {
+                        return StatusCode::SERVICE_UNAVAILABLE.into_response();
+                    }
+                    Json(serde_json::json!({ "success": true, "randomNumber": 7 })).into_response()
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (format!("http://{}", addr), requests)
+    }
+
+    #[tokio::test]
+    async fn test_transient_failure_is_retried() {
+        let policy = RetryPolicy { attempts: 3, initial_backoff: Duration::from_millis(1) };
+        let (url, requests) = spawn_flaky_server(1).await;
+        assert_eq!(get_random_number_with_retry(&url, &policy).await.unwrap(), 7);
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+        // Gives up once the attempts run out
+        let (url, requests) = spawn_flaky_server(5).await;
+        assert!(get_random_number_with_retry(&url, &policy).await.is_err());
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+    }
+
+    // Feeds the given digits to uniform_below, failing once they run out
+    async fn draw_from_digits(bound: u64, digits: &[u32]) -> eyre::Result<u64> {
+        let mut digits = digits.iter().copied();
+        uniform_below(bound, || {
+            let digit = digits.next();
+            async move { digit.ok_or_else(|| eyre::eyre!("Out of digits")) }
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_uniform_draw_reaches_past_a_single_digit() {
+        // Two digits cover a 25-tile board, so the last tile is reachable
+        assert_eq!(draw_from_digits(25, &[9, 9]).await.unwrap(), 24);
+        assert_eq!(draw_from_digits(25, &[1, 3]).await.unwrap(), 13);
+
+        // 95 is past 92, the largest multiple of 23 below 100, so it is redrawn
+        assert_eq!(draw_from_digits(23, &[9, 5, 0, 3]).await.unwrap(), 3);
+
+        assert!(draw_from_digits(25, &[10, 0]).await.is_err());
+        assert!(draw_from_digits(0, &[1]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_uniform_draw_from_random_digits_is_unbiased() {
+        let bound = 23;
+        let mut counts = vec![0u32; bound as usize];
+        for _ in 0..23_000 {
+            let value = uniform_below(bound, || async { Ok(rand::thread_rng().gen_range(0..10)) })
+                .await
+                .unwrap();
+            counts[value as usize] += 1;
+        }
+        // Each value expects 1000 draws; a digit-sized modulo would leave 10..23 empty
+        assert!(counts.iter().all(|&count| (800..1200).contains(&count)), "{:?}", counts);
+    }
+}
@@ -0,0 +1,164 @@
+use crate::{
+    apex::GameSession as ApexSession,
+    mines::GameSession as MinesSession,
+    primitives::Amount,
+    server::{AppState, Service},
+    store::{GameTransaction, StoreResult, StoredSession},
+};
+use chrono::Utc;
+use sqlx::types::BigDecimal;
+use std::{sync::Arc, time::Duration};
+use tokio::{sync::Notify, task::JoinHandle, time};
+use tracing::{error, info, warn};
+
+// How often idle sessions are looked for; the timeout itself is configured
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+// Background task resolving games the player walked away from
+pub struct SessionSweeper {
+    shutdown: Arc<Notify>,
+    task: JoinHandle<()>,
+}
+
+impl SessionSweeper {
+    pub fn start(state: Arc<AppState>, timeout: Duration) -> Self {
+        info!("Resolving sessions idle for more than {} seconds", timeout.as_secs());
+        let shutdown = Arc::new(Notify::new());
+        let stop = Arc::clone(&shutdown);
+        let task = tokio::spawn(async move {
+            let mut interval = time::interval(SWEEP_INTERVAL);
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    _ = stop.notified() => break,
+                }
+                match resolve_abandoned_sessions(&state, timeout).await {
+                    Ok(0) => {}
+                    Ok(resolved) => info!("Resolved {} abandoned sessions", resolved),
+                    Err(e) => error!("Error looking for abandoned sessions: {}", e),
+                }
+            }
+        });
+        Self { shutdown, task }
+    }
+
+    // Stop looking and let a sweep in progress finish
+    pub async fn stop(self) {
+        self.shutdown.notify_one();
+        if let Err(e) = self.task.await {
+            error!("Session sweeper task failed: {}", e);
+        }
+    }
+}
+
+// End every session idle for longer than `timeout`. A game that risked nothing yet is
+// refunded like a cancel; one that has started is forfeited, keeping the stake already
+// recorded as a loss. Returns how many sessions were resolved
+pub async fn resolve_abandoned_sessions(state: &AppState, timeout: Duration) -> StoreResult<usize> {
+    let cutoff = Utc::now() - chrono::Duration::seconds(timeout.as_secs() as i64);
+    let mut resolved = 0;
+    for stale in state.store.get_sessions_idle_since(cutoff).await? {
+        match resolve_session(state, &stale).await {
+            Ok(true) => resolved += 1,
+            Ok(false) => {}
+            Err(e) => error!("Failed to resolve abandoned session {}: {}", stale.session_id, e),
+        }
+    }
+    Ok(resolved)
+}
+
+// False when the session was played or ended while we waited for it
+async fn resolve_session(state: &AppState, stale: &StoredSession) -> StoreResult<bool> {
+    let Some(service) = Service::from_game_type(&stale.game_type) else {
+        warn!("Session {} has unknown game type {}", stale.session_id, stale.game_type);
+        return Ok(false);
+    };
+
+    // The lock only covers this instance, so compare with the database rather than our
+    // cache: another instance may have played the game since it was listed
+    let _guard = state.lock_session(&stale.session_id).await;
+    let current = state
+        .store
+        .load_session(service.game_type(), &stale.user_id, &stale.session_id)
+        .await?;
+    if current.as_ref() != Some(&stale.session_data) {
+        return Ok(false);
+    }
+
+    let Some((stake, refund, bonus_wagered)) = abandoned_bet(&service, stale.session_data.clone()) else {
+        warn!("Session {} could not be decoded", stale.session_id);
+        return Ok(false);
+    };
+
+    // Removed before crediting so a failure further on can't refund the same game twice.
+    // Only the caller that removes it resolves it, as another sweeper or a cashout on
+    // another instance may have got there first
+    let claimed = state
+        .remove_session(&service, &stale.user_id, &stale.session_id)
+        .await?;
+    if !claimed {
+        return Ok(false);
+    }
+
+    let game = match service {
+        Service::Mines => "Mines",
+        Service::Apex => "Apex",
+    };
+    let (transaction_type, amount, description) = match &refund {
+        Some(refund) => {
+            state.store.adjust_in_game_balance(&stale.user_id, refund).await?;
+            if bonus_wagered > BigDecimal::from(0) {
+                state.store.add_bonus_wagering(&stale.user_id, &bonus_wagered).await?;
+            }
+            ("refund", refund.clone(), format!("{} game abandoned - refunded bet of {}", game, refund))
+        }
+        // Already taken with the bet; recorded so the game is seen to have ended
+        None => ("forfeit", stake.clone(), format!("{} game abandoned - forfeited bet of {}", game, stake)),
+    };
+    state
+        .store
+        .create_transaction(&GameTransaction {
+            id: String::new(),
+            user_id: stale.user_id.clone(),
+            transaction_type: transaction_type.to_string(),
+            amount,
+            game_type: Some(service.game_type().to_string()),
+            game_session_id: Some(stale.session_id.clone()),
+            description: Some(description),
+            created_at: None,
+        })
+        .await?;
+
+    state
+        .publish_game_update(
+            &stale.session_id,
+            serde_json::json!({
+                "id": stale.session_id,
+                "session_status": "Ended",
+                "abandoned": true,
+                "refunded": refund,
+            }),
+            true,
+        )
+        .await;
+    Ok(true)
+}
+
+// The bet riding on an abandoned session, the refund owed on it if any, and how much
+// bonus wagering the bet counted for
+fn abandoned_bet(
+    service: &Service,
+    data: serde_json::Value,
+) -> Option<(BigDecimal, Option<BigDecimal>, BigDecimal)> {
+    match service {
+        Service::Mines => {
+            let session: MinesSession = serde_json::from_value(data).ok()?;
+            Some((session.src.clone(), session.abandoned_refund(), session.bonus_wagered))
+        }
+        Service::Apex => {
+            let session: ApexSession = serde_json::from_value(data).ok()?;
+            let stake = Amount::try_from(session.stake()).ok()?.into_inner();
+            Some((stake, session.abandoned_refund(), session.bonus_wagered))
+        }
+    }
+}
@@ -0,0 +1,38 @@
+use sqlx::types::BigDecimal;
+
+// Store failures, classified so callers can tell a missing row or a duplicate apart
+// from the database being unreachable
+#[derive(Debug, thiserror::Error)]
+pub enum StoreError {
+    #[error("Record not found")]
+    NotFound,
+    #[error("{0}")]
+    UniqueViolation(sqlx::Error),
+    #[error("Record was changed by another request")]
+    Conflict, // Write based on a stale read
+    #[error("Transaction amount must be positive, got {0}")]
+    NonPositiveAmount(BigDecimal),
+    #[error("{0}")]
+    Connection(sqlx::Error),
+    #[error("{0}")]
+    Other(sqlx::Error),
+}
+
+pub type StoreResult<T> = Result<T, StoreError>;
+
+impl From<sqlx::Error> for StoreError {
+    fn from(e: sqlx::Error) -> Self {
+        match e {
+            sqlx::Error::RowNotFound => StoreError::NotFound,
+            sqlx::Error::Io(_)
+            | sqlx::Error::Tls(_)
+            | sqlx::Error::PoolTimedOut
+            | sqlx::Error::PoolClosed
+            | sqlx::Error::WorkerCrashed => StoreError::Connection(e),
+            sqlx::Error::Database(ref db_err) if db_err.is_unique_violation() => {
+                StoreError::UniqueViolation(e)
+            }
+            e => StoreError::Other(e),
+        }
+    }
+}
@@ -1,7 +1,10 @@
 mod db_store;
+mod error;
 pub use db_store::*;
+pub use error::*;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use bigdecimal::ToPrimitive;
 use sqlx::types::BigDecimal;
 
 #[derive(Clone, Serialize, Deserialize, sqlx::FromRow)]
@@ -14,6 +17,13 @@ pub struct User {
     pub original_wallet_addr: Option<String>,
     pub account_balance: BigDecimal,
     pub in_game_balance: BigDecimal,
+    pub btc_addr: Option<String>,
+    pub btc_pk: Option<String>, // Only set when the server generated the BTC address
+    pub btc_account_balance: BigDecimal, // BTC balances move independently of the EVM ones
+    pub btc_in_game_balance: BigDecimal,
+    pub bonus_wagering_remaining: BigDecimal, // Bets still to be placed before bonus funds can be cashed out
+    #[serde(with = "chrono::serde::ts_seconds_option")]
+    pub deleted_at: Option<DateTime<Utc>>, // Set once the account is closed
     #[serde(with = "chrono::serde::ts_seconds_option")]
     pub created_at: Option<DateTime<Utc>>,
     #[serde(with = "chrono::serde::ts_seconds_option")]
@@ -33,6 +43,109 @@ pub struct GameTransaction {
     pub created_at: Option<DateTime<Utc>>,
 }
 
+// A persisted game session as stored, before it is decoded for its game
+#[derive(Clone, sqlx::FromRow)]
+pub struct StoredSession {
+    pub session_id: String,
+    pub user_id: String,
+    pub game_type: String,
+    pub session_data: serde_json::Value,
+}
+
+// Outcome of a resolved apex round
+#[derive(Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ApexRound {
+    pub id: String,
+    pub session_id: String,
+    pub round: i32, // Counting from 1, past the first only in multi-round matches
+    pub user_id: String,
+    pub option: String, // "Blinder" or "NonBlinder"
+    pub system_number: i32,
+    pub user_number: i32,
+    pub choice: Option<String>, // None for blinder rounds
+    pub won: bool,
+    pub payout: BigDecimal, // Credited amount, after the max payout clamp
+    #[serde(with = "chrono::serde::ts_seconds_option")]
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+// A cashout on its way to the user's original wallet
+#[derive(Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Withdrawal {
+    pub id: String,
+    pub user_id: String,
+    pub recipient: String,
+    pub amount: BigDecimal, // Deducted from the in-game balance, fee included
+    pub net_amount: BigDecimal, // Sent to the recipient
+    pub fee: BigDecimal,
+    pub gas_limit: i64,
+    pub gas_price: BigDecimal, // Wei, pinned at request time so the fee charged matches the fee paid
+    pub status: String, // "pending", "sending", "broadcast", "confirmed" or "failed"
+    pub tx_hash: Option<String>,
+    pub error: Option<String>,
+    pub raw_tx: Option<String>, // Signed transfer, kept so a retry sends the same one
+    pub nonce: Option<i64>,
+    #[serde(with = "chrono::serde::ts_seconds_option")]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(with = "chrono::serde::ts_seconds_option")]
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+// A user's stored in-game balance next to the one the ledger implies
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Reconciliation {
+    pub user_id: String,
+    pub in_game_balance: BigDecimal,
+    pub expected_balance: BigDecimal,
+    pub discrepancy: BigDecimal, // Stored minus expected; zero when the two agree
+}
+
+// Wager totals for one game, or for all games combined
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct GameStats {
+    pub total_wagered: BigDecimal,
+    pub total_won: BigDecimal,
+    pub total_lost: BigDecimal, // Stakes on rounds that paid nothing back
+    pub net_profit: BigDecimal,
+}
+
+impl GameStats {
+    // Share of the amount wagered that was paid back out, None before any wager
+    pub fn rtp(&self) -> Option<f64> {
+        if self.total_wagered <= BigDecimal::from(0) {
+            return None;
+        }
+        (&self.total_won / &self.total_wagered).to_f64()
+    }
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct UserStats {
+    #[serde(flatten)]
+    pub overall: GameStats,
+    pub mines: GameStats,
+    pub apex: GameStats,
+}
+
+// One finished game, summed from its ledger entries
+#[derive(Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct GameSummary {
+    pub session_id: String,
+    pub game_type: String,
+    pub outcome: String, // "won", "lost" or "refunded"
+    pub bet: BigDecimal,
+    pub payout: BigDecimal, // Winnings, or the stake handed back for a refunded game
+    pub net: BigDecimal,
+    #[serde(with = "chrono::serde::ts_seconds_option")]
+    pub played_at: Option<DateTime<Utc>>, // When the game settled
+}
+
+#[derive(Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct LeaderboardEntry {
+    pub username: String,
+    pub net_profit: BigDecimal,
+}
+
 impl User {
     pub fn new(
         user_id: String,
@@ -53,6 +166,12 @@ impl User {
             original_wallet_addr,
             account_balance,
             in_game_balance,
+            btc_addr: None,
+            btc_pk: None,
+            btc_account_balance: BigDecimal::from(0),
+            btc_in_game_balance: BigDecimal::from(0),
+            bonus_wagering_remaining: BigDecimal::from(0),
+            deleted_at: None,
             created_at: None,
             updated_at: None,
         }
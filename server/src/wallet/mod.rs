@@ -1,5 +1,8 @@
 mod router;
 mod wallet;
 
-pub use router::router;
-pub use wallet::{connect_wallet, WalletConnectionRequest, WalletConnectionResponse};
+pub use router::{ARB_SEPOLIA_RPC, authenticated_router, router};
+pub use wallet::{
+    connect_wallet, find_or_create_wallet_user, validate_btc_address, validate_evm_address, WalletConnectionRequest,
+    WalletConnectionResponse, WalletGenerator,
+};
@@ -1,6 +1,12 @@
-use crate::store::{Store, User};
-use alloy::signers::local::LocalSigner;
-use garden::api::primitives::{ApiResult, Response};
+use crate::{
+    auth::Caller,
+    primitives::CodedResult,
+    store::{Store, User},
+};
+use alloy::{primitives::Address, signers::local::LocalSigner};
+use bitcoin::{CompressedPublicKey, Network, PrivateKey, secp256k1::{Secp256k1, SecretKey}};
+use rand::RngCore;
+use garden::api::primitives::Response;
 use serde::{Deserialize, Serialize};
 use sqlx::types::BigDecimal;
 
@@ -23,6 +29,9 @@ pub struct WalletConnectionResponse {
 // Wallet generation utilities
 pub struct WalletGenerator;
 
+// BTC addresses live on testnet, alongside the Arbitrum Sepolia game wallets
+pub const BTC_NETWORK: Network = Network::Testnet;
+
 impl WalletGenerator {
 
 
@@ -34,57 +43,102 @@ impl WalletGenerator {
 
         Ok((private_key, format!("0x{:x}", address)))
     }
+
+    // Generate a new Bitcoin keypair, returning the WIF key and its P2WPKH address
+    pub fn generate_btc_wallet() -> Result<(String, String), Box<dyn std::error::Error>> {
+        let secp = Secp256k1::new();
+        let mut bytes = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let private_key = PrivateKey::new(SecretKey::from_slice(&bytes)?, BTC_NETWORK);
+        let public_key = CompressedPublicKey::from_private_key(&secp, &private_key)?;
+        let address = bitcoin::Address::p2wpkh(&public_key, BTC_NETWORK);
+
+        Ok((private_key.to_wif(), address.to_string()))
+    }
 }
 
-// Wallet connection handler
+// Reject anything that isn't a valid address on the BTC network we run against
+pub fn validate_btc_address(address: &str) -> Result<(), Response<()>> {
+    address
+        .parse::<bitcoin::Address<bitcoin::address::NetworkUnchecked>>()
+        .ok()
+        .filter(|address| address.is_valid_for_network(BTC_NETWORK))
+        .map(|_| ())
+        .ok_or_else(|| garden::api::bad_request("invalid BTC address"))
+}
+
+// Reject anything that isn't a 0x-prefixed 20-byte hex address. Mixed-case input must
+// carry a valid EIP-55 checksum; all-lowercase or all-uppercase input isn't checksummed
+pub fn validate_evm_address(address: &str) -> Result<(), Response<()>> {
+    let invalid = || garden::api::bad_request("invalid address");
+    let hex = address.strip_prefix("0x").ok_or_else(invalid)?;
+    if hex.len() != 40 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(invalid());
+    }
+
+    let mixed_case = hex.chars().any(|c| c.is_ascii_lowercase())
+        && hex.chars().any(|c| c.is_ascii_uppercase());
+    if mixed_case && Address::parse_checksummed(address, None).is_err() {
+        return Err(invalid());
+    }
+    Ok(())
+}
+
+// Wallet connection handler. The response carries the game wallet's private key, so a
+// token only gets back the wallet it signed in with; the admin may connect any wallet
 pub async fn connect_wallet(
     wallet_address: String,
+    caller: &Caller,
+    store: &Store,
+) -> CodedResult<WalletConnectionResponse> {
+    validate_evm_address(&wallet_address)?;
+    let (user, is_new_user) = match caller {
+        Caller::User(_) => (caller.account(store, &wallet_address).await?, false),
+        Caller::Admin => find_or_create_wallet_user(&wallet_address, store).await?,
+    };
+
+    let user_id = user.user_id.clone();
+    Ok(Response::ok(WalletConnectionResponse {
+        user_id: user_id.clone(),
+        game_private_key: user.pk.clone(),
+        game_public_key: user_id.clone(), // Using user_id as public key for now
+        game_evm_address: user.evm_addr.clone(),
+        is_new_user,
+    }))
+}
+
+// Look up the user for a connected wallet, creating one with a fresh game wallet if
+// needed. The flag is true when the user was just created
+pub async fn find_or_create_wallet_user(
+    wallet_address: &str,
     store: &Store,
-) -> ApiResult<WalletConnectionResponse> {
+) -> Result<(User, bool), Response<()>> {
     // Check if user already exists with this wallet address
-    let existing_user = store.get_user_by_wallet_addr(&wallet_address).await
+    let existing_user = store.get_user_by_wallet_addr(wallet_address).await
         .map_err(|e| garden::api::internal_error(&format!("Database error: {}", e)))?;
 
     if let Some(user) = existing_user {
-        // User already exists, return existing game wallet info
-        let user_id = user.user_id.clone();
-        return Ok(Response::ok(WalletConnectionResponse {
-            user_id: user_id.clone(),
-            game_private_key: user.pk.clone(),
-            game_public_key: user_id.clone(), // Using user_id as public key for now
-            game_evm_address: user.evm_addr.clone(),
-            is_new_user: false,
-        }));
+        return Ok((user, false));
     }
 
-
-
     let (evm_private_key, evm_address) = WalletGenerator::generate_evm_wallet().await
         .map_err(|e| garden::api::internal_error(&format!("Failed to generate EVM wallet: {}", e)))?;
 
     // Create new user record using wallet address as unique username
     let new_user = User::new(
         String::new(), // user_id will be generated by database
-        wallet_address.clone(), // username = wallet address (already unique)
+        wallet_address.to_string(), // username = wallet address (already unique)
         String::new(), // password (not needed for wallet users)
         evm_private_key, // pk (game private key)
         evm_address, // evm_addr (game EVM address)
-        Some(wallet_address.clone()), // original_wallet_addr (the wallet they connected with)
+        Some(wallet_address.to_string()), // original_wallet_addr (the wallet they connected with)
         BigDecimal::from(0), // account_balance
         BigDecimal::from(0), // in_game_balance
     );
 
     let created_user = store.create_user(&new_user).await
         .map_err(|e| garden::api::internal_error(&format!("Failed to create user: {}", e)))?;
-
-    let user_id = created_user.user_id.clone();
-    Ok(Response::ok(WalletConnectionResponse {
-        user_id: user_id.clone(),
-        game_private_key: created_user.pk.clone(),
-        game_public_key: user_id.clone(),
-        game_evm_address: created_user.evm_addr.clone(),
-        is_new_user: true,
-    }))
+    Ok((created_user, true))
 }
 
 #[cfg(test)]
@@ -92,6 +146,18 @@ mod tests {
     use super::*;
 
 
+    #[test]
+    fn test_validate_evm_address() {
+        // EIP-55 example address, in checksummed and plain forms
+        assert!(validate_evm_address("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed").is_ok());
+        assert!(validate_evm_address("0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed").is_ok());
+
+        assert!(validate_evm_address("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAeD").is_err()); // bad checksum
+        assert!(validate_evm_address("5aaeb6053f3e94c9b9a09f33669435e7ef1beaed").is_err());
+        assert!(validate_evm_address("0x5aaeb6053f3e94c9b9a09f33669435e7ef1bea").is_err());
+        assert!(validate_evm_address("0xzzzzb6053f3e94c9b9a09f33669435e7ef1beaed").is_err());
+    }
+
     #[tokio::test]
     async fn test_generate_evm_wallet() {
         let result = WalletGenerator::generate_evm_wallet().await;
@@ -0,0 +1,5 @@
+mod monitor;
+mod types;
+
+pub use monitor::WithdrawalMonitor;
+pub use types::*;
@@ -0,0 +1,343 @@
+use crate::{
+    store::{GameTransaction, Store, Withdrawal},
+    withdrawal_monitor::{FailedWithdrawal, WithdrawalMonitorConfig, WithdrawalResult},
+};
+use alloy::{
+    eips::eip2718::Encodable2718,
+    network::{EthereumWallet, TransactionBuilder},
+    primitives::{Address, B256, hex, utils::parse_ether},
+    providers::{Provider, ProviderBuilder},
+    rpc::types::TransactionRequest,
+    signers::local::PrivateKeySigner,
+};
+use bigdecimal::ToPrimitive;
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{sync::Notify, task::JoinHandle, time};
+use tracing::{debug, error, info, warn};
+
+pub struct WithdrawalMonitor {
+    store: Arc<Store>,
+    config: WithdrawalMonitorConfig,
+    is_running: Arc<Mutex<bool>>,
+    shutdown: Arc<Notify>, // Wakes the background loop so stop() doesn't wait out an interval
+    task: Arc<Mutex<Option<JoinHandle<()>>>>,
+}
+
+impl WithdrawalMonitor {
+    pub fn new(store: Arc<Store>, config: WithdrawalMonitorConfig) -> Self {
+        Self {
+            store,
+            config,
+            is_running: Arc::new(Mutex::new(false)),
+            shutdown: Arc::new(Notify::new()),
+            task: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub async fn start(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        {
+            let mut running = self.is_running.lock().unwrap();
+            if *running {
+                warn!("Withdrawal monitor is already running");
+                return Ok(());
+            }
+            *running = true;
+        }
+
+        info!(
+            "Starting withdrawal monitor with {} second intervals",
+            self.config.check_interval_secs
+        );
+
+        let store = Arc::clone(&self.store);
+        let config = self.config.clone();
+        let is_running = Arc::clone(&self.is_running);
+        let shutdown = Arc::clone(&self.shutdown);
+        let task = Arc::clone(&self.task);
+
+        let handle = tokio::spawn(async move {
+            let mut interval = time::interval(Duration::from_secs(config.check_interval_secs));
+
+            loop {
+                {
+                    let running = is_running.lock().unwrap();
+                    if !*running {
+                        break;
+                    }
+                }
+
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    _ = shutdown.notified() => break,
+                }
+
+                let monitor = WithdrawalMonitor {
+                    store: Arc::clone(&store),
+                    config: config.clone(),
+                    is_running: Arc::clone(&is_running),
+                    shutdown: Arc::clone(&shutdown),
+                    task: Arc::clone(&task),
+                };
+
+                match monitor.process_withdrawals().await {
+                    Ok(result) => {
+                        for id in &result.broadcast {
+                            info!("Broadcast withdrawal {}", id);
+                        }
+                        for id in &result.confirmed {
+                            info!("Confirmed withdrawal {}", id);
+                        }
+                        for failed in &result.failed {
+                            error!("Withdrawal {} failed - {}", failed.withdrawal_id, failed.error);
+                        }
+                    }
+                    Err(e) => {
+                        error!("Error during withdrawal check: {}", e);
+                    }
+                }
+            }
+
+            info!("Withdrawal monitor stopped");
+        });
+        *self.task.lock().unwrap() = Some(handle);
+
+        Ok(())
+    }
+
+    // Stop the background loop and wait for any in-flight check to finish
+    pub async fn stop(&self) {
+        {
+            let mut running = self.is_running.lock().unwrap();
+            *running = false;
+        }
+        info!("Withdrawal monitor stop requested");
+        self.shutdown.notify_one();
+
+        let handle = self.task.lock().unwrap().take();
+        if let Some(handle) = handle {
+            if let Err(e) = handle.await {
+                error!("Withdrawal monitor task failed: {}", e);
+            }
+        }
+    }
+
+    // Broadcast pending withdrawals and resend interrupted ones, then look for receipts of broadcast ones
+    pub async fn process_withdrawals(
+        &self,
+    ) -> Result<WithdrawalResult, Box<dyn std::error::Error + Send + Sync>> {
+        debug!("Starting withdrawal check cycle");
+        let mut result = WithdrawalResult::default();
+
+        let pending = self.store.get_withdrawals_by_status("pending").await?;
+        let sending = self.store.get_withdrawals_by_status("sending").await?;
+        let broadcast = self.store.get_withdrawals_by_status("broadcast").await?;
+        for withdrawal in pending.iter().chain(&sending).chain(&broadcast) {
+            match self.process_withdrawal(withdrawal).await {
+                Ok(updated) if updated.status == "broadcast" && withdrawal.status != "broadcast" => {
+                    result.broadcast.push(updated.id)
+                }
+                Ok(updated) if updated.status == "confirmed" => result.confirmed.push(updated.id),
+                Ok(updated) if updated.status == "failed" => result.failed.push(FailedWithdrawal {
+                    withdrawal_id: updated.id,
+                    error: updated.error.unwrap_or_default(),
+                }),
+                Ok(_) => {}
+                Err(e) => result.failed.push(FailedWithdrawal {
+                    withdrawal_id: withdrawal.id.clone(),
+                    error: e.to_string(),
+                }),
+            }
+        }
+
+        Ok(result)
+    }
+
+    // Move one withdrawal a step along: send it if pending or still sending, confirm it if
+    // broadcast. Returns the row as it now stands
+    pub async fn process_withdrawal(
+        &self,
+        withdrawal: &Withdrawal,
+    ) -> Result<Withdrawal, Box<dyn std::error::Error + Send + Sync>> {
+        match withdrawal.status.as_str() {
+            "pending" | "sending" => self.broadcast_withdrawal(withdrawal).await,
+            "broadcast" => self.check_confirmation(withdrawal).await,
+            _ => Ok(withdrawal.clone()),
+        }
+    }
+
+    // Send a pending withdrawal, or resend one an interrupted cycle left sending. The row is
+    // claimed and its signed transfer stored before anything goes out, so a retry reuses the
+    // same nonce and the user is paid at most once. A transfer the node rejects outright
+    // leaves the game address untouched, so the in-game balance is given back
+    async fn broadcast_withdrawal(
+        &self,
+        withdrawal: &Withdrawal,
+    ) -> Result<Withdrawal, Box<dyn std::error::Error + Send + Sync>> {
+        let withdrawal = if withdrawal.status == "pending" {
+            match self.store.claim_withdrawal(&withdrawal.id).await? {
+                Some(claimed) => claimed,
+                None => return Ok(withdrawal.clone()),
+            }
+        } else {
+            withdrawal.clone()
+        };
+        let user = self
+            .store
+            .get_user_by_id(&withdrawal.user_id)
+            .await?
+            .ok_or_else(|| format!("User not found: {}", withdrawal.user_id))?;
+
+        let provider = ProviderBuilder::new().connect_http(self.config.rpc_url.parse()?);
+        let withdrawal = match withdrawal.raw_tx {
+            Some(_) => withdrawal,
+            None => {
+                let (raw_tx, tx_hash, nonce) = self.sign_native_transfer(&provider, &user.pk, &withdrawal).await?;
+                self.store
+                    .record_signed_withdrawal(&withdrawal.id, &raw_tx, &tx_hash, nonce as i64)
+                    .await?
+            }
+        };
+        let raw_tx = withdrawal.raw_tx.as_deref().ok_or("Signed withdrawal has no raw tx")?;
+        let tx_hash = withdrawal.tx_hash.clone().ok_or("Signed withdrawal has no tx hash")?;
+
+        if let Err(e) = provider.send_raw_transaction(&hex::decode(raw_tx)?).await {
+            // A resend the node already holds, or has mined, is not a rejection
+            let known = provider.get_transaction_by_hash(tx_hash.parse()?).await?.is_some();
+            if !known {
+                if e.as_error_resp().is_none() {
+                    // The node may not have seen it, so it stays sending for the next cycle
+                    return Err(e.into());
+                }
+                self.store
+                    .adjust_in_game_balance(&withdrawal.user_id, &withdrawal.amount)
+                    .await?;
+                let updated = self
+                    .store
+                    .fail_unsent_withdrawal(&withdrawal.id, &e.to_string())
+                    .await?;
+                return Ok(updated);
+            }
+        }
+        let updated = self
+            .store
+            .update_withdrawal_status(&withdrawal.id, "broadcast", None, None)
+            .await?;
+
+        // The transfer and its fee leave the game address, so later refreshes must not see
+        // the lower chain balance as missing funds
+        self.store
+            .adjust_on_chain_last_seen(&user.evm_addr, &(-withdrawal.amount.clone()))
+            .await?;
+
+        let transaction = GameTransaction {
+            id: String::new(),
+            user_id: withdrawal.user_id.clone(),
+            transaction_type: "cashout".to_string(),
+            amount: withdrawal.amount.clone(),
+            game_type: None,
+            game_session_id: None,
+            description: Some(format!(
+                "Cashout to original wallet: {} - sent {} after {} network fee - tx: {}",
+                withdrawal.recipient, withdrawal.net_amount, withdrawal.fee, tx_hash
+            )),
+            created_at: None,
+        };
+        self.store.create_transaction(&transaction).await?;
+
+        Ok(updated)
+    }
+
+    // Sign the transfer with the gas pinned at request time and the next nonce of the game
+    // address, returning the raw transaction, its hash and the nonce
+    async fn sign_native_transfer(
+        &self,
+        provider: &impl Provider,
+        private_key: &str,
+        withdrawal: &Withdrawal,
+    ) -> Result<(String, String, u64), Box<dyn std::error::Error + Send + Sync>> {
+        let signer: PrivateKeySigner = private_key.parse()?;
+        let to: Address = withdrawal.recipient.parse()?;
+        let value = parse_ether(&withdrawal.net_amount.to_string())?;
+        let gas_price = withdrawal
+            .gas_price
+            .to_u128()
+            .ok_or("Invalid gas price")?;
+
+        let nonce = provider.get_transaction_count(signer.address()).pending().await?;
+        let chain_id = provider.get_chain_id().await?;
+        let tx = TransactionRequest::default()
+            .with_from(signer.address())
+            .with_to(to)
+            .with_value(value)
+            .with_gas_limit(withdrawal.gas_limit as u64)
+            .with_gas_price(gas_price)
+            .with_nonce(nonce)
+            .with_chain_id(chain_id);
+        let envelope = tx.build(&EthereumWallet::from(signer)).await?;
+
+        Ok((
+            hex::encode_prefixed(envelope.encoded_2718()),
+            envelope.tx_hash().to_string(),
+            nonce,
+        ))
+    }
+
+    // Confirm a broadcast withdrawal once it has enough confirmations, or fail it if it
+    // reverted. Left as is while the transfer is still waiting
+    async fn check_confirmation(
+        &self,
+        withdrawal: &Withdrawal,
+    ) -> Result<Withdrawal, Box<dyn std::error::Error + Send + Sync>> {
+        let tx_hash: B256 = withdrawal
+            .tx_hash
+            .as_deref()
+            .ok_or("Broadcast withdrawal has no tx hash")?
+            .parse()?;
+        let provider = ProviderBuilder::new().connect_http(self.config.rpc_url.parse()?);
+        let Some(receipt) = provider.get_transaction_receipt(tx_hash).await? else {
+            return Ok(withdrawal.clone());
+        };
+
+        if !receipt.status() {
+            // Only the fee was spent, so the transfer amount goes back to the player
+            let user = self
+                .store
+                .get_user_by_id(&withdrawal.user_id)
+                .await?
+                .ok_or_else(|| format!("User not found: {}", withdrawal.user_id))?;
+            self.store
+                .adjust_in_game_balance(&withdrawal.user_id, &withdrawal.net_amount)
+                .await?;
+            self.store
+                .adjust_on_chain_last_seen(&user.evm_addr, &withdrawal.net_amount)
+                .await?;
+            let updated = self
+                .store
+                .update_withdrawal_status(&withdrawal.id, "failed", None, Some("Transaction reverted"))
+                .await?;
+            return Ok(updated);
+        }
+
+        let Some(block) = receipt.block_number else {
+            return Ok(withdrawal.clone());
+        };
+        let head_block = provider.get_block_number().await?;
+        let confirmations = head_block.saturating_sub(block) + 1;
+        if confirmations < self.config.required_confirmations {
+            debug!(
+                "Withdrawal {} has {} of {} confirmations",
+                withdrawal.id, confirmations, self.config.required_confirmations
+            );
+            return Ok(withdrawal.clone());
+        }
+
+        let updated = self
+            .store
+            .update_withdrawal_status(&withdrawal.id, "confirmed", None, None)
+            .await?;
+        Ok(updated)
+    }
+}
@@ -0,0 +1,21 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone)]
+pub struct WithdrawalMonitorConfig {
+    pub check_interval_secs: u64,
+    pub required_confirmations: u64,
+    pub rpc_url: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WithdrawalResult {
+    pub broadcast: Vec<String>, // Withdrawal ids sent this cycle
+    pub confirmed: Vec<String>,
+    pub failed: Vec<FailedWithdrawal>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FailedWithdrawal {
+    pub withdrawal_id: String,
+    pub error: String,
+}